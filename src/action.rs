@@ -1,8 +1,20 @@
 use crate::http::{Request, Response};
+use std::future::Future;
 
 #[crate::async_trait]
 pub trait Action {
     async fn call(&self, req: Request) -> Response;
 }
 
+#[crate::async_trait]
+impl<F, Fut> Action for F
+where
+    F: Fn(Request) -> Fut + Send + Sync,
+    Fut: Future<Output = Response> + Send,
+{
+    async fn call(&self, req: Request) -> Response {
+        self(req).await
+    }
+}
+
 pub type BoxedAction = Box<dyn Action + Send + Sync>;
@@ -1,10 +1,17 @@
+use cookie::Key;
 use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct Config {
   pub(crate) address: IpAddr,
   pub(crate) port: u16,
   pub(crate) keep_alive: Option<u64>,
+  pub(crate) timeout: Option<Duration>,
+  pub(crate) body_limit: Option<u64>,
+  pub(crate) body_read_timeout: Option<Duration>,
+  pub(crate) body_min_rate: Option<(u64, Duration)>,
+  pub(crate) secret_key: Option<Key>,
 }
 
 impl Default for Config {
@@ -13,6 +20,11 @@ impl Default for Config {
       address: Ipv4Addr::new(127, 0, 0, 1).into(),
       port: 8000,
       keep_alive: Some(5),
+      timeout: None,
+      body_limit: Some(2 * 1024 * 1024),
+      body_read_timeout: None,
+      body_min_rate: None,
+      secret_key: None,
     }
   }
 }
@@ -28,6 +40,50 @@ impl Config {
     self
   }
 
+  /// Sets how long a route may take to respond before the request is
+  /// cancelled with a `503 Service Unavailable` (default is no timeout).
+  /// Routes can override this with [`Route::timeout`](crate::router::Route::timeout).
+  pub fn timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+    self.timeout = timeout.into();
+    self
+  }
+
+  /// Sets the maximum size of a request body in bytes (default is 2 MiB).
+  /// Larger requests are answered with `413 Payload Too Large`. Routes can
+  /// override this with [`Route::body_limit`](crate::router::Route::body_limit).
+  ///
+  /// The default applies to every route, including ones that accept
+  /// uploads. Pass `None` to leave request bodies unbounded.
+  pub fn body_limit(mut self, bytes: impl Into<Option<u64>>) -> Self {
+    self.body_limit = bytes.into();
+    self
+  }
+
+  /// Sets how long to wait for the next chunk of a request body before
+  /// answering with `408 Request Timeout` (default is no timeout).
+  pub fn body_read_timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+    self.body_read_timeout = timeout.into();
+    self
+  }
+
+  /// Sets the minimum rate in bytes per second at which a request body must
+  /// be received once the grace period has passed, answering slower requests
+  /// with `408 Request Timeout`.
+  pub fn body_min_rate(mut self, bytes_per_second: u64, grace_period: Duration) -> Self {
+    self.body_min_rate = Some((bytes_per_second, grace_period));
+    self
+  }
+
+  /// Sets the key used to sign and encrypt cookies in the
+  /// [`signed`](crate::http::CookieJar::signed) and
+  /// [`private`](crate::http::CookieJar::private) jars (default is none).
+  /// The key must be kept secret and stay the same across restarts, or
+  /// existing cookies will be rejected.
+  pub fn secret_key(mut self, key: Key) -> Self {
+    self.secret_key = Some(key);
+    self
+  }
+
   /// Sets the port to serve on
   pub fn port(mut self, port: u16) -> Self {
     self.port = port;
@@ -0,0 +1,99 @@
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+pub use tokio::time::error::Elapsed;
+
+/// The point in time by which a request must be handled.
+///
+/// When a timeout is configured, the deadline is stored in the request
+/// extensions before the middleware stack runs, so handlers can bound their
+/// own work, such as database queries, by the time remaining:
+///
+/// ```rust
+/// use turbofish::deadline::Deadline;
+/// use turbofish::http::{Request, Response};
+///
+/// async fn handler(req: Request) -> Response {
+///   if let Some(deadline) = req.extensions().get::<Deadline>() {
+///     let remaining = deadline.remaining();
+///     // ...
+///   }
+///   Response::new("done")
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Deadline(Instant);
+
+impl Deadline {
+  /// Returns a deadline `timeout` from now.
+  pub fn after(timeout: Duration) -> Self {
+    Self(Instant::now() + timeout)
+  }
+
+  /// Returns the instant at which the deadline expires.
+  pub fn instant(&self) -> Instant {
+    self.0
+  }
+
+  /// Returns the time remaining until the deadline expires, or zero if it
+  /// already has.
+  pub fn remaining(&self) -> Duration {
+    self.0.saturating_duration_since(Instant::now())
+  }
+
+  pub fn is_expired(&self) -> bool {
+    self.0 <= Instant::now()
+  }
+
+  /// Runs the future to completion, cancelling it if the deadline expires
+  /// first.
+  pub async fn run<F: Future>(&self, fut: F) -> Result<F::Output, Elapsed> {
+    tokio::time::timeout_at(self.0.into(), fut).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::Config;
+  use crate::http::{Method, Request, Response, StatusCode};
+  use crate::router::{Route, Router};
+
+  fn request(uri: &str) -> Request {
+    hyper::Request::builder()
+      .uri(uri)
+      .body(hyper::Body::empty())
+      .unwrap()
+      .into()
+  }
+
+  async fn slow(req: Request) -> Response {
+    let deadline = req.extensions().get::<Deadline>().unwrap();
+    assert!(deadline.remaining() <= Duration::from_millis(50));
+    tokio::time::sleep(Duration::from_secs(5)).await;
+    Response::new("slow")
+  }
+
+  #[tokio::test]
+  async fn timeout() {
+    let mut router = Router::default();
+    router.route(Route::new(Method::GET, "/global", slow));
+    router.route(Route::new(Method::GET, "/route", slow).timeout(Duration::from_millis(10)));
+    router.route(Route::new(Method::GET, "/fast", |_: Request| async {
+      Response::new("fast")
+    }));
+
+    let config = Config::default().timeout(Duration::from_millis(50));
+    let res = router.serve(request("/global"), &config).await.unwrap();
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let res = router
+      .serve(request("/route"), &Config::default())
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let res = router.serve(request("/fast"), &config).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+  }
+}
@@ -0,0 +1,799 @@
+//! Static file serving.
+
+use crate::action::Action;
+use crate::http::{header, Body, HeaderMap, HeaderValue, Method, Request, Response, StatusCode};
+use crate::router::Params;
+use bytes::Bytes;
+use futures::{StreamExt, TryStreamExt};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use std::error::Error;
+use std::fs::Metadata;
+use std::io::SeekFrom;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+/// Serves files from a directory, see [`Router::files`](crate::router::Router::files).
+///
+/// The file path is read from the catch-all parameter of the route, which
+/// must be the last parameter in the path, ex: `/assets/*path`.
+///
+/// `ETag` and `Last-Modified` validators are sent with every file, and
+/// conditional and `Range` requests are supported. If the client accepts
+/// it, a precompressed `.br` or `.gz` sibling of the file is served instead.
+///
+/// ```rust
+/// use turbofish::files::Files;
+/// use turbofish::http::Method;
+/// use turbofish::router::{Route, Router};
+///
+/// let mut router = Router::default();
+/// router.route(Route::new(
+///   Method::GET,
+///   "/public/*path",
+///   Files::new("./public").listing(true),
+/// ));
+/// ```
+#[derive(Clone)]
+pub struct Files {
+  inner: Arc<Inner>,
+}
+
+struct Inner {
+  root: PathBuf,
+  index: Option<String>,
+  listing: bool,
+  precompressed: bool,
+}
+
+// the maximum number of ranges served in a single multipart response
+const MAX_RANGES: usize = 16;
+
+impl Files {
+  /// Serves files from the `root` directory.
+  pub fn new(root: impl Into<PathBuf>) -> Self {
+    Self {
+      inner: Arc::new(Inner {
+        root: root.into(),
+        index: Some("index.html".to_owned()),
+        listing: false,
+        precompressed: true,
+      }),
+    }
+  }
+
+  /// Sets the file served for directories (default is `index.html`).
+  pub fn index(mut self, index: Option<&str>) -> Self {
+    self.inner_mut().index = index.map(str::to_owned);
+    self
+  }
+
+  /// Sets whether to render a listing of directories that do not have an
+  /// index file (default is false).
+  pub fn listing(mut self, listing: bool) -> Self {
+    self.inner_mut().listing = listing;
+    self
+  }
+
+  /// Sets whether to serve precompressed `.br` and `.gz` siblings of files
+  /// (default is true).
+  pub fn precompressed(mut self, precompressed: bool) -> Self {
+    self.inner_mut().precompressed = precompressed;
+    self
+  }
+
+  fn inner_mut(&mut self) -> &mut Inner {
+    Arc::get_mut(&mut self.inner).expect("`Files` options must be set before it is cloned")
+  }
+
+  /// Resolves the request path to a file path under the root directory,
+  /// rejecting paths that would escape it.
+  fn resolve(&self, path: &str) -> Option<PathBuf> {
+    let decoded = percent_decode_str(path).decode_utf8().ok()?;
+    let mut resolved = self.inner.root.clone();
+
+    for segment in decoded.split('/') {
+      match Path::new(segment).components().next() {
+        None | Some(Component::CurDir) => {}
+        Some(Component::Normal(name)) if name == segment && !segment.contains('\\') => {
+          resolved.push(segment)
+        }
+        // `..`, roots, and windows prefixes
+        _ => return None,
+      }
+    }
+
+    Some(resolved)
+  }
+
+  async fn serve(&self, req: Request) -> Result<Response, Box<dyn Error + Send + Sync>> {
+    let param = req
+      .extensions()
+      .get::<Params>()
+      .and_then(|params| params.0.last())
+      .map(|param| param.value.as_str())
+      .unwrap_or("/");
+
+    let mut path = match self.resolve(param) {
+      Some(path) => path,
+      None => return Ok(status(StatusCode::NOT_FOUND)),
+    };
+
+    // symlinks could point outside of the root
+    let root = tokio::fs::canonicalize(&self.inner.root).await?;
+    if !contained(&root, &path).await {
+      return Ok(status(StatusCode::NOT_FOUND));
+    }
+
+    let mut metadata = tokio::fs::metadata(&path).await?;
+
+    if metadata.is_dir() {
+      // relative links in the index must resolve to the directory
+      if !req.path().ends_with('/') {
+        let location = format!("{}/", req.path());
+        return Ok(
+          Response::builder()
+            .status(StatusCode::MOVED_PERMANENTLY)
+            .header(header::LOCATION, location)
+            .body(Body::empty())?,
+        );
+      }
+
+      let index = self.inner.index.as_ref().map(|index| path.join(index));
+      match index {
+        Some(index)
+          if contained(&root, &index).await
+            && tokio::fs::metadata(&index).await.is_ok_and(|m| m.is_file()) =>
+        {
+          path = index;
+          metadata = tokio::fs::metadata(&path).await?;
+        }
+        _ if self.inner.listing => {
+          return listing(&path, req.path(), path != self.inner.root).await
+        }
+        _ => return Ok(status(StatusCode::NOT_FOUND)),
+      }
+    }
+
+    let mime = mime_guess::from_path(&path).first_or_octet_stream();
+    let mut encoding = None;
+
+    if self.inner.precompressed {
+      for (name, ext) in &[("br", "br"), ("gzip", "gz")] {
+        if !accepts_encoding(req.headers(), name) {
+          continue;
+        }
+
+        let mut compressed = path.clone().into_os_string();
+        compressed.push(".");
+        compressed.push(ext);
+
+        if !contained(&root, Path::new(&compressed)).await {
+          continue;
+        }
+
+        if let Ok(m) = tokio::fs::metadata(&compressed).await {
+          if m.is_file() {
+            path = compressed.into();
+            metadata = m;
+            encoding = Some(*name);
+            break;
+          }
+        }
+      }
+    }
+
+    let file = FileInfo::new(&metadata, encoding);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_str(mime.as_ref())?);
+    headers.insert(header::ETAG, HeaderValue::from_str(&file.etag)?);
+    headers.insert(
+      header::LAST_MODIFIED,
+      HeaderValue::from_str(&httpdate::fmt_http_date(file.modified))?,
+    );
+    headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    if self.inner.precompressed {
+      headers.insert(header::VARY, HeaderValue::from_static("Accept-Encoding"));
+    }
+    if let Some(encoding) = encoding {
+      headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static(encoding));
+    }
+
+    if file.not_modified(req.headers()) {
+      headers.remove(header::CONTENT_TYPE);
+      return Ok(with_headers(status(StatusCode::NOT_MODIFIED), headers));
+    }
+
+    let ranges = match req.headers().get(header::RANGE) {
+      Some(range) if file.if_range(req.headers()) => parse_ranges(range, file.len),
+      _ => None,
+    };
+
+    let head = req.method() == Method::HEAD;
+
+    let res = match ranges {
+      Some(Err(Unsatisfiable)) => {
+        headers.insert(
+          header::CONTENT_RANGE,
+          format!("bytes */{}", file.len).parse()?,
+        );
+        status(StatusCode::RANGE_NOT_SATISFIABLE)
+      }
+      Some(Ok(ranges)) if ranges.len() == 1 => {
+        let (start, end) = ranges[0];
+        headers.insert(
+          header::CONTENT_RANGE,
+          format!("bytes {}-{}/{}", start, end, file.len).parse()?,
+        );
+        headers.insert(header::CONTENT_LENGTH, (end - start + 1).into());
+
+        let body = if head {
+          Body::empty()
+        } else {
+          Body::Streamed(Box::pin(section(path, start, end - start + 1)))
+        };
+
+        Response::builder()
+          .status(StatusCode::PARTIAL_CONTENT)
+          .body(body)?
+      }
+      Some(Ok(ranges)) => {
+        // random, so that a file can't be crafted to contain the boundary
+        let boundary = format!("{:032x}", rand::random::<u128>());
+        let content_type = headers.remove(header::CONTENT_TYPE).unwrap();
+        let body = multipart(path, &ranges, &boundary, content_type.to_str()?, file.len);
+
+        headers.insert(header::CONTENT_LENGTH, body.len.into());
+        headers.insert(
+          header::CONTENT_TYPE,
+          format!("multipart/byteranges; boundary={}", boundary).parse()?,
+        );
+
+        let body = if head { Body::empty() } else { body.body };
+        Response::builder()
+          .status(StatusCode::PARTIAL_CONTENT)
+          .body(body)?
+      }
+      None => {
+        headers.insert(header::CONTENT_LENGTH, file.len.into());
+        let body = if head {
+          Body::empty()
+        } else {
+          Body::Streamed(Box::pin(section(path, 0, file.len)))
+        };
+        Response::new(body)
+      }
+    };
+
+    Ok(with_headers(res, headers))
+  }
+}
+
+#[crate::async_trait]
+impl Action for Files {
+  async fn call(&self, req: Request) -> Response {
+    let path = req.path().to_owned();
+    match self.serve(req).await {
+      Ok(res) => res,
+      Err(err) => match err
+        .downcast_ref::<std::io::Error>()
+        .map(std::io::Error::kind)
+      {
+        Some(std::io::ErrorKind::NotFound) => status(StatusCode::NOT_FOUND),
+        Some(std::io::ErrorKind::PermissionDenied) => status(StatusCode::FORBIDDEN),
+        _ => {
+          log::error!("failed to serve file `{}`: {}", path, err);
+          status(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+      },
+    }
+  }
+}
+
+struct FileInfo {
+  len: u64,
+  modified: SystemTime,
+  modified_nanos: u64,
+  etag: String,
+}
+
+impl FileInfo {
+  fn new(metadata: &Metadata, encoding: Option<&str>) -> Self {
+    let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+    let modified_nanos = modified
+      .duration_since(UNIX_EPOCH)
+      .map_or(0, |d| d.as_nanos() as u64);
+
+    // each encoding is a different representation
+    let etag = match encoding {
+      Some(encoding) => format!("\"{:x}-{:x}-{}\"", modified_nanos, metadata.len(), encoding),
+      None => format!("\"{:x}-{:x}\"", modified_nanos, metadata.len()),
+    };
+
+    Self {
+      len: metadata.len(),
+      modified,
+      modified_nanos,
+      etag,
+    }
+  }
+
+  /// Evaluates `If-None-Match`, or `If-Modified-Since` in its absence.
+  fn not_modified(&self, headers: &HeaderMap) -> bool {
+    if let Some(etags) = headers.get(header::IF_NONE_MATCH) {
+      return etags.to_str().is_ok_and(|etags| {
+        etags.trim() == "*"
+          || etags
+            .split(',')
+            .any(|etag| weak_eq(etag.trim(), &self.etag))
+      });
+    }
+
+    headers
+      .get(header::IF_MODIFIED_SINCE)
+      .and_then(|since| httpdate::parse_http_date(since.to_str().ok()?).ok())
+      .is_some_and(|since| self.modified_secs() <= since)
+  }
+
+  /// Evaluates `If-Range`, returning whether the range should be served.
+  fn if_range(&self, headers: &HeaderMap) -> bool {
+    let if_range = match headers.get(header::IF_RANGE).and_then(|v| v.to_str().ok()) {
+      Some(if_range) => if_range.trim(),
+      None => return true,
+    };
+
+    if if_range.starts_with('"') {
+      // requires a strong comparison
+      return if_range == self.etag;
+    }
+
+    httpdate::parse_http_date(if_range).is_ok_and(|date| self.modified_secs() == date)
+  }
+
+  // http dates have a resolution of one second
+  fn modified_secs(&self) -> SystemTime {
+    UNIX_EPOCH + std::time::Duration::from_secs(self.modified_nanos / 1_000_000_000)
+  }
+}
+
+fn weak_eq(a: &str, b: &str) -> bool {
+  a.trim_start_matches("W/") == b.trim_start_matches("W/")
+}
+
+fn accepts_encoding(headers: &HeaderMap, encoding: &str) -> bool {
+  headers
+    .get_all(header::ACCEPT_ENCODING)
+    .iter()
+    .filter_map(|value| value.to_str().ok())
+    .flat_map(|value| value.split(','))
+    .any(|coding| {
+      let mut parts = coding.split(';');
+      let name = parts.next().unwrap_or("").trim();
+      let rejected = parts.any(|param| {
+        let param = param.trim();
+        param.starts_with("q=") && param[2..].parse::<f32>().is_ok_and(|q| q == 0.0)
+      });
+      name.eq_ignore_ascii_case(encoding) && !rejected
+    })
+}
+
+#[derive(Debug, PartialEq)]
+struct Unsatisfiable;
+
+/// Parses a `Range` header into inclusive byte ranges. Returns `None` if the
+/// header should be ignored.
+fn parse_ranges(header: &HeaderValue, len: u64) -> Option<Result<Vec<(u64, u64)>, Unsatisfiable>> {
+  let specs = header.to_str().ok()?.trim().strip_prefix("bytes=")?;
+  let mut ranges = Vec::new();
+
+  for spec in specs
+    .split(',')
+    .map(str::trim)
+    .filter(|spec| !spec.is_empty())
+  {
+    let (start, end) = spec.split_at(spec.find('-')?);
+    let end = &end[1..];
+
+    let range = match (start.trim(), end.trim()) {
+      ("", suffix) => {
+        let suffix = suffix.parse::<u64>().ok()?;
+        if suffix == 0 || len == 0 {
+          continue;
+        }
+        (len.saturating_sub(suffix), len - 1)
+      }
+      (start, end) => {
+        let start = start.parse::<u64>().ok()?;
+        let end = match end {
+          "" => u64::MAX,
+          end => end.parse::<u64>().ok()?,
+        };
+        if start > end {
+          return None;
+        }
+        if start >= len {
+          continue;
+        }
+        (start, end.min(len - 1))
+      }
+    };
+
+    ranges.push(range);
+  }
+
+  if ranges.len() > MAX_RANGES {
+    return None;
+  }
+
+  if ranges.is_empty() {
+    return Some(Err(Unsatisfiable));
+  }
+
+  Some(Ok(ranges))
+}
+
+type ByteStream = futures::stream::BoxStream<'static, Result<Bytes, Box<dyn Error + Send + Sync>>>;
+
+/// Streams `len` bytes of the file starting at `start`. The file is opened
+/// lazily when the stream is first polled.
+fn section(path: PathBuf, start: u64, len: u64) -> ByteStream {
+  let open = async move {
+    let mut file = tokio::fs::File::open(path).await?;
+    file.seek(SeekFrom::Start(start)).await?;
+    Ok::<_, std::io::Error>(ReaderStream::new(file.take(len)))
+  };
+
+  futures::stream::once(open)
+    .try_flatten()
+    .map_err(Into::into)
+    .boxed()
+}
+
+struct Multipart {
+  body: Body,
+  len: u64,
+}
+
+/// Builds a `multipart/byteranges` body.
+fn multipart(
+  path: PathBuf,
+  ranges: &[(u64, u64)],
+  boundary: &str,
+  content_type: &str,
+  len: u64,
+) -> Multipart {
+  let mut parts = Vec::with_capacity(ranges.len() * 2 + 1);
+  let mut total = 0;
+
+  for &(start, end) in ranges {
+    let head = format!(
+      "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+      boundary, content_type, start, end, len
+    );
+    total += head.len() as u64 + (end - start + 1);
+    parts.push(futures::stream::once(futures::future::ok(Bytes::from(head))).boxed());
+    parts.push(section(path.clone(), start, end - start + 1));
+  }
+
+  let tail = format!("\r\n--{}--\r\n", boundary);
+  total += tail.len() as u64;
+  parts.push(futures::stream::once(futures::future::ok(Bytes::from(tail))).boxed());
+
+  Multipart {
+    body: Body::Streamed(Box::pin(futures::stream::iter(parts).flatten())),
+    len: total,
+  }
+}
+
+// characters escaped in the links of a directory listing
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+  .add(b' ')
+  .add(b'"')
+  .add(b'#')
+  .add(b'%')
+  .add(b'<')
+  .add(b'>')
+  .add(b'?')
+  .add(b'`')
+  .add(b'{')
+  .add(b'}')
+  .add(b'/');
+
+/// Returns whether `path` exists and resolves, following symlinks, to a
+/// path under the canonical `root`.
+async fn contained(root: &Path, path: &Path) -> bool {
+  match tokio::fs::canonicalize(path).await {
+    Ok(canonical) => canonical.starts_with(root),
+    Err(_) => false,
+  }
+}
+
+async fn listing(
+  dir: &Path,
+  path: &str,
+  parent: bool,
+) -> Result<Response, Box<dyn Error + Send + Sync>> {
+  let mut entries = Vec::new();
+  let mut read_dir = tokio::fs::read_dir(dir).await?;
+
+  while let Some(entry) = read_dir.next_entry().await? {
+    let name = entry.file_name().to_string_lossy().into_owned();
+    let is_dir = entry.file_type().await?.is_dir();
+    entries.push((name, is_dir));
+  }
+
+  entries.sort();
+
+  let title = escape(&percent_decode_str(path).decode_utf8_lossy());
+  let mut html = format!(
+    "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Index of {0}</title></head>\n<body>\n<h1>Index of {0}</h1>\n<ul>\n",
+    title
+  );
+
+  // the mount root has no parent to link to
+  if parent {
+    html.push_str("<li><a href=\"../\">../</a></li>\n");
+  }
+
+  for (name, is_dir) in entries {
+    let slash = if is_dir { "/" } else { "" };
+    html.push_str(&format!(
+      "<li><a href=\"{}{}\">{}{}</a></li>\n",
+      utf8_percent_encode(&name, PATH_SEGMENT),
+      slash,
+      escape(&name),
+      slash
+    ));
+  }
+
+  html.push_str("</ul>\n</body>\n</html>\n");
+
+  Ok(
+    Response::builder()
+      .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+      .body(html)?,
+  )
+}
+
+fn escape(s: &str) -> String {
+  let mut escaped = String::with_capacity(s.len());
+  for c in s.chars() {
+    match c {
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      '"' => escaped.push_str("&quot;"),
+      '\'' => escaped.push_str("&#39;"),
+      c => escaped.push(c),
+    }
+  }
+  escaped
+}
+
+fn status(status: StatusCode) -> Response {
+  Response::builder()
+    .status(status)
+    .body(Body::empty())
+    .unwrap()
+}
+
+fn with_headers(mut res: Response, headers: HeaderMap) -> Response {
+  res.headers_mut().extend(headers);
+  res
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::Config;
+  use crate::router::{Route, Router};
+
+  struct Fixture {
+    _dir: tempfile::TempDir,
+    router: Router,
+  }
+
+  fn fixture(options: impl FnOnce(Files) -> Files) -> Fixture {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("hello.txt"), "hello world").unwrap();
+    std::fs::write(dir.path().join("app.js"), "console.log(1)").unwrap();
+    std::fs::write(dir.path().join("app.js.gz"), "gzipped").unwrap();
+    std::fs::create_dir(dir.path().join("docs")).unwrap();
+    std::fs::write(dir.path().join("docs").join("index.html"), "<h1>docs</h1>").unwrap();
+    std::fs::create_dir(dir.path().join("empty")).unwrap();
+
+    let mut router = Router::default();
+    let files = options(Files::new(dir.path()));
+    router.route(Route::new(Method::GET, "/assets/*path", files));
+
+    Fixture { _dir: dir, router }
+  }
+
+  async fn get(router: &Router, uri: &str, headers: &[(&str, &str)]) -> (Response, String) {
+    let mut req = hyper::Request::builder().uri(uri);
+    for (key, value) in headers {
+      req = req.header(*key, *value);
+    }
+
+    let req = req.body(hyper::Body::empty()).unwrap().into();
+    let mut res = router.serve(req, &Config::default()).await.unwrap();
+    let body = std::mem::take(res.body_mut())
+      .bytes()
+      .await
+      .unwrap()
+      .to_vec();
+
+    (res, String::from_utf8(body).unwrap())
+  }
+
+  #[tokio::test]
+  async fn serve_files() {
+    let f = fixture(|files| files);
+
+    let (res, body) = get(&f.router, "/assets/hello.txt", &[]).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["content-type"], "text/plain");
+    assert_eq!(res.headers()["content-length"], "11");
+    assert_eq!(body, "hello world");
+
+    let etag = res.headers()["etag"].to_str().unwrap().to_owned();
+    let (res, _) = get(&f.router, "/assets/hello.txt", &[("If-None-Match", &etag)]).await;
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+    let modified = res.headers()["last-modified"].to_str().unwrap().to_owned();
+    let (res, _) = get(
+      &f.router,
+      "/assets/hello.txt",
+      &[("If-Modified-Since", &modified)],
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+    let (res, body) = get(
+      &f.router,
+      "/assets/app.js",
+      &[("Accept-Encoding", "br, gzip")],
+    )
+    .await;
+    assert_eq!(res.headers()["content-encoding"], "gzip");
+    assert_eq!(res.headers()["content-type"], "text/javascript");
+    assert_eq!(body, "gzipped");
+
+    let (res, body) = get(&f.router, "/assets/docs/", &[]).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(body, "<h1>docs</h1>");
+
+    let (res, _) = get(&f.router, "/assets/docs", &[]).await;
+    assert_eq!(res.status(), StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(res.headers()["location"], "/assets/docs/");
+
+    let (res, _) = get(&f.router, "/assets/empty/", &[]).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let (res, _) = get(&f.router, "/assets/missing.txt", &[]).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+  }
+
+  #[tokio::test]
+  async fn traversal() {
+    let f = fixture(|files| files);
+
+    for path in &[
+      "/assets/../Cargo.toml",
+      "/assets/%2e%2e/Cargo.toml",
+      "/assets/docs/..%2f..%2fCargo.toml",
+    ] {
+      let (res, _) = get(&f.router, path, &[]).await;
+      assert_eq!(res.status(), StatusCode::NOT_FOUND, "{}", path);
+    }
+  }
+
+  #[tokio::test]
+  async fn listing() {
+    let f = fixture(|files| files.index(None).listing(true));
+
+    let (res, body) = get(&f.router, "/assets/", &[]).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(body.contains("<a href=\"docs/\">docs/</a>"));
+    assert!(body.contains("<a href=\"hello.txt\">hello.txt</a>"));
+    assert!(!body.contains("../"));
+
+    let (res, body) = get(&f.router, "/assets/empty/", &[]).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(body.contains("<a href=\"../\">../</a>"));
+  }
+
+  #[cfg(unix)]
+  #[tokio::test]
+  async fn symlinks() {
+    let outside = tempfile::tempdir().unwrap();
+    let secret = outside.path().join("secret");
+    std::fs::write(&secret, "secret").unwrap();
+
+    let f = fixture(|files| files.listing(true));
+    let root = f._dir.path();
+    std::os::unix::fs::symlink(&secret, root.join("link.txt")).unwrap();
+    std::os::unix::fs::symlink(&secret, root.join("hello.txt.gz")).unwrap();
+    std::fs::create_dir(root.join("linked")).unwrap();
+    std::os::unix::fs::symlink(&secret, root.join("linked").join("index.html")).unwrap();
+
+    let (res, _) = get(&f.router, "/assets/link.txt", &[]).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let (res, body) = get(
+      &f.router,
+      "/assets/hello.txt",
+      &[("Accept-Encoding", "gzip")],
+    )
+    .await;
+    assert!(res.headers().get("content-encoding").is_none());
+    assert_eq!(body, "hello world");
+
+    let (res, body) = get(&f.router, "/assets/linked/", &[]).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(body.starts_with("<!DOCTYPE html>"));
+  }
+
+  #[tokio::test]
+  async fn ranges() {
+    let f = fixture(|files| files);
+
+    let (res, body) = get(&f.router, "/assets/hello.txt", &[("Range", "bytes=0-4")]).await;
+    assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(res.headers()["content-range"], "bytes 0-4/11");
+    assert_eq!(body, "hello");
+
+    let (_, body) = get(&f.router, "/assets/hello.txt", &[("Range", "bytes=-5")]).await;
+    assert_eq!(body, "world");
+
+    let (res, _) = get(&f.router, "/assets/hello.txt", &[("Range", "bytes=20-")]).await;
+    assert_eq!(res.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(res.headers()["content-range"], "bytes */11");
+
+    let (res, body) = get(&f.router, "/assets/hello.txt", &[("Range", "bytes=0-1,6-")]).await;
+    assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+    let content_type = res.headers()["content-type"].to_str().unwrap();
+    let boundary = content_type
+      .strip_prefix("multipart/byteranges; boundary=")
+      .unwrap();
+    assert_eq!(
+      res.headers()["content-length"],
+      body.len().to_string().as_str()
+    );
+    assert_eq!(
+      body,
+      format!(
+        "\r\n--{0}\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-1/11\r\n\r\nhe\
+         \r\n--{0}\r\nContent-Type: text/plain\r\nContent-Range: bytes 6-10/11\r\n\r\nworld\
+         \r\n--{0}--\r\n",
+        boundary
+      )
+    );
+
+    let (res, _) = get(&f.router, "/assets/hello.txt", &[("Range", "bytes=0-1,6-")]).await;
+    assert_ne!(res.headers()["content-type"], content_type);
+
+    // stale validators ignore the range
+    let (res, _) = get(
+      &f.router,
+      "/assets/hello.txt",
+      &[("Range", "bytes=0-4"), ("If-Range", "\"stale\"")],
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+  }
+
+  #[test]
+  fn range_parsing() {
+    let parse = |range: &'static str| parse_ranges(&HeaderValue::from_static(range), 100);
+    assert_eq!(parse("bytes=0-9"), Some(Ok(vec![(0, 9)])));
+    assert_eq!(parse("bytes=90-200"), Some(Ok(vec![(90, 99)])));
+    assert_eq!(parse("bytes=-10, 5-"), Some(Ok(vec![(90, 99), (5, 99)])));
+    assert_eq!(parse("bytes=100-"), Some(Err(Unsatisfiable)));
+    assert_eq!(parse("bytes=5-1"), None);
+    assert_eq!(parse("items=0-1"), None);
+  }
+}
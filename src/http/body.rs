@@ -1,11 +1,532 @@
+use crate::http::{BodyLimitError, HeaderMap, StatusCode};
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
 use std::pin::Pin;
-use bytes::Bytes;
-use futures::Stream;
+use std::task::{Context, Poll};
+use bytes::{Bytes, BytesMut};
+use futures::{ready, Stream, StreamExt, TryStreamExt};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
 
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// The body of a request or response.
+///
+/// A body can be read chunk by chunk with [`chunk`](Body::chunk), or as a
+/// [`Stream`], or all at once with [`bytes`](Body::bytes). Request bodies
+/// are subject to the limits set with
+/// [`Config::body_limit`](crate::config::Config::body_limit) and
+/// [`Route::body_limit`](crate::router::Route::body_limit); reading past
+/// them fails with [`BodyError::Limit`].
+///
+/// Response bodies that are produced over time, such as large exports, are
+/// best created with [`producer`](Body::producer) or
+/// [`channel`](Body::channel), which wait for the client to keep up, can
+/// end with trailers and notice when the client disconnects.
+#[derive(Default)]
 pub enum Body {
+  #[default]
   Empty,
   Once(Bytes),
-  Streamed(
-    Pin<Box<dyn Stream<Item = Result<Bytes, Box<dyn std::error::Error + Send + Sync>>> + Send>>,
-  ),
+  Streamed(Pin<Box<dyn Stream<Item = Result<Bytes, BoxError>> + Send>>),
+  Channel(ChannelBody),
+}
+
+impl Body {
+  pub fn empty() -> Self {
+    Body::Empty
+  }
+
+  pub fn stream<S, B, E>(stream: S) -> Self
+  where
+    S: Stream<Item = Result<B, E>> + Send + 'static,
+    B: Into<Bytes> + 'static,
+    E: Into<BoxError> + 'static,
+  {
+    Body::Streamed(Box::pin(stream.map_ok(Into::into).map_err(Into::into)))
+  }
+
+  /// Creates a body streamed through the returned [`BodySender`].
+  pub fn channel() -> (BodySender, Self) {
+    // a single chunk is buffered, so the sender waits for the connection
+    let (tx, rx) = mpsc::channel(1);
+    let body = ChannelBody {
+      rx,
+      trailers: None,
+      done: false,
+      producer: None,
+    };
+    (BodySender { tx }, Body::Channel(body))
+  }
+
+  /// Creates a body written by `producer`, which is spawned as a task.
+  ///
+  /// The task is aborted when the body is dropped, which hyper does when
+  /// the client disconnects, so the producer doesn't keep running for
+  /// nobody. If the producer returns an error or panics, the response is
+  /// aborted and the client sees it as incomplete.
+  ///
+  /// ```rust
+  /// use turbofish::http::{Body, Response};
+  ///
+  /// # async fn rows(_: u64) -> Vec<String> { Vec::new() }
+  /// async fn export() -> Response {
+  ///   let body = Body::producer(|mut sender| async move {
+  ///     sender.send("id,name\n").await?;
+  ///     for page in 0.. {
+  ///       let rows = rows(page).await;
+  ///       if rows.is_empty() {
+  ///         return Ok(());
+  ///       }
+  ///       sender.send(rows.concat()).await?;
+  ///     }
+  ///     Ok::<_, turbofish::http::Disconnected>(())
+  ///   });
+  ///
+  ///   Response::builder().header("content-type", "text/csv").body(body).unwrap()
+  /// }
+  /// ```
+  ///
+  /// # Panics
+  ///
+  /// Panics if called outside of a Tokio runtime.
+  pub fn producer<F, Fut, E>(producer: F) -> Self
+  where
+    F: FnOnce(BodySender) -> Fut,
+    Fut: Future<Output = Result<(), E>> + Send + 'static,
+    E: Into<BoxError>,
+  {
+    let (sender, mut body) = Body::channel();
+    let errors = sender.tx.clone();
+    let producer = producer(sender);
+
+    let handle = tokio::spawn(async move {
+      let err = match producer.await {
+        Ok(()) => return,
+        Err(err) => err.into(),
+      };
+      let _ = errors.send(Frame::Error(err)).await;
+    });
+
+    if let Body::Channel(channel) = &mut body {
+      channel.producer = Some(handle);
+    }
+    body
+  }
+
+  /// Returns the next chunk of the body, or `None` once the body has been
+  /// read completely.
+  pub async fn chunk(&mut self) -> Option<Result<Bytes, BodyError>> {
+    self.next().await
+  }
+
+  /// Reads the whole body into memory.
+  pub async fn bytes(self) -> Result<Bytes, BodyError> {
+    match self {
+      Body::Empty => Ok(Bytes::new()),
+      Body::Once(bytes) => Ok(bytes),
+      body => {
+        let bytes = body
+          .try_fold(BytesMut::new(), |mut acc, chunk| async move {
+            acc.extend_from_slice(&chunk);
+            Ok(acc)
+          })
+          .await?;
+        Ok(bytes.freeze())
+      }
+    }
+  }
+}
+
+impl Stream for Body {
+  type Item = Result<Bytes, BodyError>;
+
+  fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    hyper::body::HttpBody::poll_data(self, cx)
+      .map(|chunk| chunk.map(|chunk| chunk.map_err(BodyError::from_stream)))
+  }
+}
+
+impl hyper::body::HttpBody for Body {
+  type Data = Bytes;
+  type Error = BoxError;
+
+  fn poll_data(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, BoxError>>> {
+    let this = self.get_mut();
+    match this {
+      Body::Empty => Poll::Ready(None),
+      Body::Once(_) => match std::mem::take(this) {
+        Body::Once(bytes) if !bytes.is_empty() => Poll::Ready(Some(Ok(bytes))),
+        _ => Poll::Ready(None),
+      },
+      Body::Streamed(stream) => stream.as_mut().poll_next(cx),
+      Body::Channel(channel) => channel.poll_data(cx),
+    }
+  }
+
+  fn poll_trailers(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<Option<HeaderMap>, BoxError>> {
+    match self.get_mut() {
+      Body::Channel(channel) => Poll::Ready(Ok(channel.trailers.take())),
+      _ => Poll::Ready(Ok(None)),
+    }
+  }
+
+  fn is_end_stream(&self) -> bool {
+    match self {
+      Body::Empty => true,
+      Body::Once(bytes) => bytes.is_empty(),
+      _ => false,
+    }
+  }
+
+  fn size_hint(&self) -> hyper::body::SizeHint {
+    match self {
+      Body::Empty => hyper::body::SizeHint::with_exact(0),
+      Body::Once(bytes) => hyper::body::SizeHint::with_exact(bytes.len() as u64),
+      _ => hyper::body::SizeHint::default(),
+    }
+  }
+}
+
+enum Frame {
+  Data(Bytes),
+  Trailers(HeaderMap),
+  Error(BoxError),
+}
+
+/// A body streamed through a [`BodySender`], created with
+/// [`Body::channel`] or [`Body::producer`].
+pub struct ChannelBody {
+  rx: mpsc::Receiver<Frame>,
+  trailers: Option<HeaderMap>,
+  done: bool,
+  producer: Option<JoinHandle<()>>,
+}
+
+impl ChannelBody {
+  fn poll_data(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, BoxError>>> {
+    if self.done {
+      return Poll::Ready(None);
+    }
+
+    match ready!(self.rx.poll_recv(cx)) {
+      Some(Frame::Data(bytes)) => Poll::Ready(Some(Ok(bytes))),
+      Some(Frame::Trailers(trailers)) => {
+        self.done = true;
+        self.trailers = Some(trailers);
+        Poll::Ready(None)
+      }
+      Some(Frame::Error(err)) => {
+        self.done = true;
+        Poll::Ready(Some(Err(err)))
+      }
+      None => {
+        // the channel also closes when the producer panics
+        if let Some(producer) = &mut self.producer {
+          let result = ready!(Pin::new(producer).poll(cx));
+          self.producer = None;
+          if let Err(err) = result {
+            self.done = true;
+            return Poll::Ready(Some(Err(err.into())));
+          }
+        }
+
+        self.done = true;
+        Poll::Ready(None)
+      }
+    }
+  }
+}
+
+impl Drop for ChannelBody {
+  fn drop(&mut self) {
+    if let Some(producer) = &self.producer {
+      producer.abort();
+    }
+  }
+}
+
+/// The sending half of a body created with [`Body::channel`] or
+/// [`Body::producer`].
+///
+/// Only one chunk is buffered at a time, so sending waits until the
+/// connection has taken the previous chunk, and a slow client slows the
+/// sender down rather than filling up memory. The body ends when the
+/// sender is dropped.
+pub struct BodySender {
+  tx: mpsc::Sender<Frame>,
+}
+
+impl BodySender {
+  /// Sends a chunk of the body, waiting until there is room for it.
+  pub async fn send(&mut self, chunk: impl Into<Bytes>) -> Result<(), Disconnected> {
+    self.tx.send(Frame::Data(chunk.into())).await.map_err(|_| Disconnected)
+  }
+
+  /// Waits until every chunk sent has been taken by the connection.
+  pub async fn flush(&mut self) -> Result<(), Disconnected> {
+    self.tx.reserve().await.map(drop).map_err(|_| Disconnected)
+  }
+
+  /// Ends the body with trailers, such as a checksum of the body.
+  ///
+  /// Trailers are only sent over HTTP/2, and are dropped on HTTP/1.1
+  /// connections.
+  pub async fn send_trailers(self, trailers: HeaderMap) -> Result<(), Disconnected> {
+    self.tx.send(Frame::Trailers(trailers)).await.map_err(|_| Disconnected)
+  }
+
+  /// Aborts the body, so that the client sees an incomplete response
+  /// instead of a complete one: the HTTP/2 stream is reset, or the
+  /// HTTP/1.1 connection closed.
+  pub async fn abort(self, err: impl Into<BoxError>) {
+    let _ = self.tx.send(Frame::Error(err.into())).await;
+  }
+
+  /// Waits until the body is dropped, such as when the client disconnects.
+  pub async fn closed(&self) {
+    self.tx.closed().await
+  }
+
+  /// Returns `true` if the body has been dropped.
+  pub fn is_closed(&self) -> bool {
+    self.tx.is_closed()
+  }
+}
+
+/// An error returned when sending to a body that has been dropped, such as
+/// when the client disconnected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Disconnected;
+
+impl fmt::Display for Disconnected {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("body receiver was dropped")
+  }
+}
+
+impl Error for Disconnected {}
+
+/// An error returned when consuming a body.
+#[derive(Debug)]
+pub enum BodyError {
+  /// The body violated a limit set in the [`Config`](crate::config::Config).
+  Limit(BodyLimitError),
+  /// The body could not be read from the connection.
+  Read(BoxError),
+  /// The `Content-Type` of the request is missing or not supported,
+  /// including unknown charsets.
+  UnsupportedMediaType,
+  /// The body is not valid text in its charset.
+  InvalidText,
+  /// The body is not valid JSON for the expected type.
+  Json(serde_json::Error),
+  /// The body is not a valid form for the expected type.
+  Form(serde_urlencoded::de::Error),
+}
+
+impl BodyError {
+  fn from_stream(err: BoxError) -> Self {
+    match err.downcast::<BodyLimitError>() {
+      Ok(err) => BodyError::Limit(*err),
+      Err(err) => BodyError::Read(err),
+    }
+  }
+
+  /// Returns the status code the request should be answered with.
+  pub fn status(&self) -> StatusCode {
+    match self {
+      BodyError::Limit(err) => err.status(),
+      BodyError::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+      _ => StatusCode::BAD_REQUEST,
+    }
+  }
+}
+
+impl fmt::Display for BodyError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      BodyError::Limit(err) => err.fmt(f),
+      BodyError::Read(err) => write!(f, "failed to read body: {}", err),
+      BodyError::UnsupportedMediaType => f.write_str("unsupported media type"),
+      BodyError::InvalidText => f.write_str("body is not valid text in its charset"),
+      BodyError::Json(err) => write!(f, "invalid json body: {}", err),
+      BodyError::Form(err) => write!(f, "invalid form body: {}", err),
+    }
+  }
+}
+
+impl Error for BodyError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match self {
+      BodyError::Limit(err) => Some(err),
+      BodyError::Read(err) => Some(&**err),
+      BodyError::Json(err) => Some(err),
+      BodyError::Form(err) => Some(err),
+      _ => None,
+    }
+  }
+}
+
+impl From<Bytes> for Body {
+  fn from(bytes: Bytes) -> Self {
+    Body::Once(bytes)
+  }
+}
+
+impl From<String> for Body {
+  fn from(s: String) -> Self {
+    Body::Once(s.into())
+  }
+}
+
+impl From<&'static str> for Body {
+  fn from(s: &'static str) -> Self {
+    Body::Once(s.into())
+  }
+}
+
+impl From<Vec<u8>> for Body {
+  fn from(v: Vec<u8>) -> Self {
+    Body::Once(v.into())
+  }
+}
+
+impl From<hyper::Body> for Body {
+  fn from(body: hyper::Body) -> Self {
+    Body::stream(body)
+  }
+}
+
+impl From<Body> for hyper::Body {
+  fn from(body: Body) -> Self {
+    match body {
+      Body::Empty => hyper::Body::empty(),
+      Body::Once(bytes) => hyper::Body::from(bytes),
+      Body::Streamed(stream) => hyper::Body::wrap_stream(stream),
+      // trailers are lost, hyper serves `Body` directly to keep them
+      body @ Body::Channel(_) => hyper::Body::wrap_stream(body),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::http::{BodyLimits, Request};
+  use serde::Deserialize;
+
+  fn request(content_type: &str, body: &'static [u8]) -> Request {
+    hyper::Request::post("/")
+      .header("content-type", content_type)
+      .body(hyper::Body::from(body))
+      .unwrap()
+      .into()
+  }
+
+  #[derive(Deserialize, Debug, PartialEq)]
+  struct Login {
+    user: String,
+    remember: bool,
+  }
+
+  #[tokio::test]
+  async fn consume() {
+    let mut req = request("text/plain; charset=iso-8859-1", b"caf\xe9");
+    assert_eq!(req.text().await.unwrap(), "café");
+
+    let mut req = request("text/plain", b"caf\xe9");
+    assert!(matches!(req.text().await, Err(BodyError::InvalidText)));
+
+    let mut req = request("text/plain; charset=klingon", b"");
+    assert_eq!(req.text().await.unwrap_err().status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let login = Login {
+      user: "ferris".to_owned(),
+      remember: true,
+    };
+
+    let mut req = request("application/json", br#"{"user":"ferris","remember":true}"#);
+    assert_eq!(req.json::<Login>().await.unwrap(), login);
+
+    let mut req = request("application/vnd.api+json", br#"{"user":"ferris","remember":true}"#);
+    assert_eq!(req.json::<Login>().await.unwrap(), login);
+
+    let mut req = request("text/plain", br#"{"user":"ferris","remember":true}"#);
+    assert_eq!(req.json::<Login>().await.unwrap_err().status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let mut req = request("application/json", br#"{"user":"ferris"}"#);
+    assert_eq!(req.json::<Login>().await.unwrap_err().status(), StatusCode::BAD_REQUEST);
+
+    let mut req = request("application/x-www-form-urlencoded", b"user=ferris&remember=true");
+    assert_eq!(req.form::<Login>().await.unwrap(), login);
+
+    let limits = BodyLimits {
+      max_size: Some(4),
+      ..BodyLimits::default()
+    };
+    let (body, _) = limits.apply(Body::stream(futures::stream::iter(vec![Ok::<_, BoxError>("hello")])));
+    let err = body.bytes().await.unwrap_err();
+    assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+  }
+
+  #[tokio::test]
+  async fn chunks() {
+    let mut body = Body::stream(futures::stream::iter(vec![Ok::<_, BoxError>("a"), Ok("b")]));
+    assert_eq!(body.chunk().await.unwrap().unwrap(), "a");
+    assert_eq!(body.chunk().await.unwrap().unwrap(), "b");
+    assert!(body.chunk().await.is_none());
+
+    let mut body = Body::from("once");
+    assert_eq!(body.chunk().await.unwrap().unwrap(), "once");
+    assert!(body.chunk().await.is_none());
+  }
+
+  #[tokio::test]
+  async fn channel() {
+    use hyper::body::HttpBody;
+
+    let (mut sender, mut body) = Body::channel();
+    sender.send("a").await.unwrap();
+
+    // the previous chunk hasn't been taken yet
+    let mut flush = Box::pin(sender.flush());
+    assert!(futures::poll!(&mut flush).is_pending());
+    assert_eq!(body.data().await.unwrap().unwrap(), "a");
+    flush.await.unwrap();
+
+    let mut trailers = HeaderMap::new();
+    trailers.insert("grpc-status", "0".parse().unwrap());
+    tokio::spawn(sender.send_trailers(trailers));
+    assert!(body.data().await.is_none());
+    assert_eq!(body.trailers().await.unwrap().unwrap()["grpc-status"], "0");
+
+    let (mut sender, body) = Body::channel();
+    drop(body);
+    assert_eq!(sender.send("a").await, Err(Disconnected));
+
+    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    let body = Body::producer(|_| async move {
+      let _tx = tx;
+      futures::future::pending::<Result<(), Disconnected>>().await
+    });
+    drop(body);
+    assert!(rx.await.is_err());
+
+    let mut body = Body::producer(|_| async { Err("export failed") });
+    assert!(body.data().await.unwrap().is_err());
+
+    let mut body = Body::producer(|mut sender| async move {
+      sender.send("a").await?;
+      if sender.send("b").await.is_ok() {
+        panic!("export failed");
+      }
+      Ok::<_, Disconnected>(())
+    });
+    assert_eq!(body.data().await.unwrap().unwrap(), "a");
+    assert_eq!(body.data().await.unwrap().unwrap(), "b");
+    assert!(body.data().await.unwrap().is_err());
+    assert!(body.data().await.is_none());
+  }
 }
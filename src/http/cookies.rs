@@ -1,4 +1,234 @@
+use crate::http::{header, HeaderMap, HeaderValue};
+use cookie::{Cookie, Key};
+use std::sync::{Arc, Mutex};
+
 /// A collection of one or more HTTP cookies.
+///
+/// The jar is filled from the `Cookie` headers of the request the first
+/// time it is read. Cookies added to or removed from the jar are sent back
+/// to the client as `Set-Cookie` headers on the response. Clones of a jar
+/// share the same cookies, so a jar taken from the request can still be
+/// written to after the request is consumed.
+///
+/// ```rust
+/// use turbofish::http::{Cookie, Request, Response};
+///
+/// async fn visit(req: Request) -> Response {
+///     let visits = req
+///         .cookies()
+///         .get("visits")
+///         .and_then(|cookie| cookie.value().parse::<u64>().ok())
+///         .unwrap_or(0);
+///
+///     req.cookies().add(Cookie::new("visits", (visits + 1).to_string()));
+///     Response::new(format!("{} visits", visits + 1))
+/// }
+/// ```
+#[derive(Clone, Default)]
 pub struct CookieJar {
-    inner: cookie::CookieJar
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    jar: cookie::CookieJar,
+    // `Cookie` header values that have not been parsed yet
+    raw: Vec<String>,
+    key: Option<Key>,
+}
+
+impl Inner {
+    fn jar(&mut self) -> &mut cookie::CookieJar {
+        for value in self.raw.drain(..) {
+            for cookie in value.split(';') {
+                if let Ok(cookie) = Cookie::parse(cookie.trim().to_owned()) {
+                    self.jar.add_original(cookie);
+                }
+            }
+        }
+
+        &mut self.jar
+    }
+}
+
+impl CookieJar {
+    /// Creates a jar holding the cookies in the `Cookie` headers.
+    pub(crate) fn from_headers(headers: &HeaderMap<HeaderValue>) -> Self {
+        let raw = headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .map(str::to_owned)
+            .collect();
+
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                raw,
+                ..Inner::default()
+            })),
+        }
+    }
+
+    /// Sets the key used for signed and private cookies.
+    pub(crate) fn set_key(&self, key: Option<Key>) {
+        self.inner.lock().unwrap().key = key;
+    }
+
+    /// Returns the cookie with the given name.
+    pub fn get(&self, name: &str) -> Option<Cookie<'static>> {
+        self.inner.lock().unwrap().jar().get(name).cloned()
+    }
+
+    /// Returns every cookie in the jar.
+    pub fn all(&self) -> Vec<Cookie<'static>> {
+        self.inner.lock().unwrap().jar().iter().cloned().collect()
+    }
+
+    /// Adds a cookie, sending it to the client.
+    pub fn add(&self, cookie: Cookie<'static>) {
+        self.inner.lock().unwrap().jar().add(cookie);
+    }
+
+    /// Removes a cookie, telling the client to remove it if it sent the
+    /// cookie with the request. The path and domain of `cookie` must match
+    /// those it was set with.
+    pub fn remove(&self, cookie: Cookie<'static>) {
+        self.inner.lock().unwrap().jar().remove(cookie);
+    }
+
+    /// Returns a view of the jar whose cookies are signed, so the client can
+    /// read but not modify them, or `None` if no
+    /// [`secret_key`](crate::config::Config::secret_key) is configured.
+    pub fn signed(&self) -> Option<SignedJar<'_>> {
+        let key = self.inner.lock().unwrap().key.clone()?;
+        Some(SignedJar { jar: self, key })
+    }
+
+    /// Returns a view of the jar whose cookies are encrypted, so the client
+    /// can neither read nor modify them, or `None` if no
+    /// [`secret_key`](crate::config::Config::secret_key) is configured.
+    pub fn private(&self) -> Option<PrivateJar<'_>> {
+        let key = self.inner.lock().unwrap().key.clone()?;
+        Some(PrivateJar { jar: self, key })
+    }
+
+    /// Returns the `Set-Cookie` header values for the cookies added or
+    /// removed since the jar was created.
+    pub(crate) fn delta(&self) -> Vec<HeaderValue> {
+        self.inner
+            .lock()
+            .unwrap()
+            .jar
+            .delta()
+            .filter_map(|cookie| HeaderValue::from_str(&cookie.to_string()).ok())
+            .collect()
+    }
+}
+
+/// A view of a [`CookieJar`] that signs cookies as they are added and
+/// verifies them as they are read.
+pub struct SignedJar<'a> {
+    jar: &'a CookieJar,
+    key: Key,
+}
+
+impl SignedJar<'_> {
+    /// Returns the cookie with the given name, if its signature is valid.
+    pub fn get(&self, name: &str) -> Option<Cookie<'static>> {
+        self.jar.inner.lock().unwrap().jar().signed(&self.key).get(name)
+    }
+
+    /// Signs and adds a cookie.
+    pub fn add(&self, cookie: Cookie<'static>) {
+        self.jar.inner.lock().unwrap().jar().signed(&self.key).add(cookie);
+    }
+
+    /// Removes a cookie.
+    pub fn remove(&self, cookie: Cookie<'static>) {
+        self.jar.remove(cookie);
+    }
+}
+
+/// A view of a [`CookieJar`] that encrypts cookies as they are added and
+/// decrypts them as they are read.
+pub struct PrivateJar<'a> {
+    jar: &'a CookieJar,
+    key: Key,
+}
+
+impl PrivateJar<'_> {
+    /// Returns the decrypted cookie with the given name, if it was
+    /// encrypted with the same key and has not been tampered with.
+    pub fn get(&self, name: &str) -> Option<Cookie<'static>> {
+        self.jar.inner.lock().unwrap().jar().private(&self.key).get(name)
+    }
+
+    /// Encrypts and adds a cookie.
+    pub fn add(&self, cookie: Cookie<'static>) {
+        self.jar.inner.lock().unwrap().jar().private(&self.key).add(cookie);
+    }
+
+    /// Removes a cookie.
+    pub fn remove(&self, cookie: Cookie<'static>) {
+        self.jar.remove(cookie);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::http::{Method, Request, Response};
+    use crate::router::{Route, Router};
+
+    #[tokio::test]
+    async fn signed() {
+        let mut router = Router::default();
+        router.route(Route::new(Method::GET, "/", |req: Request| async move {
+            let signed = req.cookies().signed().unwrap();
+            let user = signed.get("user");
+            signed.add(Cookie::new("user", "ferris"));
+            req.cookies().remove(Cookie::named("old"));
+            Response::new(user.map(|c| c.value().to_owned()).unwrap_or_default())
+        }));
+
+        let config = Config::default().secret_key(Key::generate());
+        let request = |cookie: &str| {
+            hyper::Request::builder()
+                .uri("/")
+                .header(header::COOKIE, cookie)
+                .body(hyper::Body::empty())
+                .unwrap()
+                .into()
+        };
+
+        let res = router.serve(request("old=1; user=ferris"), &config).await.unwrap();
+        let cookies: Vec<_> = res.headers().get_all(header::SET_COOKIE).iter().collect();
+        assert_eq!(cookies.len(), 2);
+
+        let user = cookies
+            .iter()
+            .find(|c| c.to_str().unwrap().starts_with("user="))
+            .unwrap();
+        assert_ne!(user.to_str().unwrap(), "user=ferris");
+
+        // the client sends back the signed value
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, user.to_str().unwrap().parse().unwrap());
+        let jar = CookieJar::from_headers(&headers);
+        jar.set_key(config.secret_key.clone());
+        assert_eq!(jar.signed().unwrap().get("user").unwrap().value(), "ferris");
+        assert!(jar.private().unwrap().get("user").is_none());
+    }
+
+    #[test]
+    fn without_key() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, HeaderValue::from_static("user=ferris"));
+        let jar = CookieJar::from_headers(&headers);
+
+        assert!(jar.signed().is_none());
+        assert!(jar.private().is_none());
+        assert_eq!(jar.get("user").unwrap().value(), "ferris");
+    }
 }
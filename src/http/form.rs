@@ -0,0 +1,346 @@
+//! A deserializer for form-like data: query strings and multipart forms.
+//!
+//! Fields are collected into a tree keyed by their names, where bracket
+//! notation (`filter[status]`) creates nested maps, indices (`items[0]`) or
+//! empty brackets (`tag[]`) create sequences, and repeated keys collect
+//! multiple values. Values are text, and are parsed into numbers or
+//! booleans when the target type asks for them.
+//!
+//! Names are limited to [`MAX_DEPTH`] segments and forms to [`MAX_FIELDS`]
+//! fields, so that untrusted input can't build arbitrarily deep or large
+//! trees.
+
+use serde::de::{self, IntoDeserializer, Unexpected, Visitor};
+use serde::{forward_to_deserialize_any, Deserializer};
+use std::collections::BTreeMap;
+
+pub(crate) type Error = de::value::Error;
+
+/// The name a file upload is requested under by
+/// [`UploadedFile`](crate::http::UploadedFile)'s `Deserialize` impl, so that
+/// only this deserializer can produce one.
+pub(crate) const UPLOAD: &str = "$turbofish::UploadedFile";
+
+/// The maximum number of segments in a field name, ex: `a[b][c]` has 3.
+pub(crate) const MAX_DEPTH: usize = 16;
+
+/// The maximum number of fields in a form.
+pub(crate) const MAX_FIELDS: usize = 1000;
+
+/// The fields of a form, collected into a tree.
+#[derive(Default)]
+pub(crate) struct Fields {
+  root: Node,
+  len: usize,
+}
+
+impl Fields {
+  /// Inserts the value of the field `name`, which may use bracket notation.
+  pub fn insert(&mut self, name: &str, value: Value) -> Result<(), Error> {
+    if self.len == MAX_FIELDS {
+      return Err(de::Error::custom(format_args!(
+        "too many fields, the limit is {}",
+        MAX_FIELDS
+      )));
+    }
+
+    self.root.insert(name, value)?;
+    self.len += 1;
+    Ok(())
+  }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Fields {
+  type Deserializer = Node;
+
+  fn into_deserializer(self) -> Node {
+    self.root
+  }
+}
+
+/// A single value of a field.
+pub(crate) enum Value {
+  Text(String),
+  /// The index of a file saved while reading a multipart form.
+  Upload(usize),
+}
+
+/// A node of the field tree.
+pub(crate) enum Node {
+  Values(Vec<Value>),
+  Map(BTreeMap<String, Node>),
+}
+
+impl Default for Node {
+  fn default() -> Self {
+    Node::Map(BTreeMap::new())
+  }
+}
+
+impl Node {
+  fn insert(&mut self, name: &str, value: Value) -> Result<(), Error> {
+    let (first, rest) = match name.find('[') {
+      Some(i) if name.ends_with(']') => (&name[..i], &name[i + 1..name.len() - 1]),
+      _ => (name, ""),
+    };
+
+    let mut node = self;
+    let segments = std::iter::once(first).chain(rest.split("][").filter(|_| !rest.is_empty()));
+
+    if segments.clone().count() > MAX_DEPTH {
+      return Err(de::Error::custom(format_args!(
+        "field `{}` is nested too deeply, the limit is {} segments",
+        name.get(..64).unwrap_or(name),
+        MAX_DEPTH
+      )));
+    }
+
+    for segment in segments {
+      // `tag[]` appends to `tag`
+      if segment.is_empty() {
+        continue;
+      }
+
+      if matches!(node, Node::Values(values) if values.is_empty()) {
+        *node = Node::default();
+      }
+
+      node = match node {
+        Node::Map(map) => map
+          .entry(segment.to_owned())
+          .or_insert_with(|| Node::Values(Vec::new())),
+        Node::Values(_) => return Err(conflict(name)),
+      };
+    }
+
+    match node {
+      Node::Values(values) => {
+        values.push(value);
+        Ok(())
+      }
+      Node::Map(_) => Err(conflict(name)),
+    }
+  }
+}
+
+fn conflict(name: &str) -> Error {
+  de::Error::custom(format_args!("field `{}` is both a value and a map", name))
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Node {
+  type Deserializer = Self;
+
+  fn into_deserializer(self) -> Self {
+    self
+  }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+  type Deserializer = Self;
+
+  fn into_deserializer(self) -> Self {
+    self
+  }
+}
+
+impl Node {
+  fn single(self) -> Result<Value, Error> {
+    match self {
+      Node::Values(mut values) if values.len() == 1 => Ok(values.pop().unwrap()),
+      Node::Values(values) => Err(de::Error::invalid_length(values.len(), &"a single value")),
+      Node::Map(_) => Err(de::Error::invalid_type(Unexpected::Map, &"a single value")),
+    }
+  }
+}
+
+macro_rules! single {
+  ($($method:ident)*) => {$(
+    fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+      self.single()?.$method(visitor)
+    }
+  )*};
+}
+
+impl<'de> Deserializer<'de> for Node {
+  type Error = Error;
+
+  fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+    match self {
+      Node::Values(values) if values.len() == 1 => {
+        Node::Values(values).single()?.deserialize_any(visitor)
+      }
+      Node::Values(values) => {
+        visitor.visit_seq(de::value::SeqDeserializer::new(values.into_iter()))
+      }
+      Node::Map(map) => visitor.visit_map(de::value::MapDeserializer::new(map.into_iter())),
+    }
+  }
+
+  fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+    match self {
+      Node::Values(values) => {
+        visitor.visit_seq(de::value::SeqDeserializer::new(values.into_iter()))
+      }
+      Node::Map(map) => {
+        // `items[0]`, `items[1]`, .. in index order
+        let mut items = Vec::with_capacity(map.len());
+        for (key, node) in map {
+          match key.parse::<usize>() {
+            Ok(index) => items.push((index, node)),
+            Err(_) => return Err(de::Error::invalid_type(Unexpected::Map, &visitor)),
+          }
+        }
+        items.sort_by_key(|(index, _)| *index);
+        visitor.visit_seq(de::value::SeqDeserializer::new(
+          items.into_iter().map(|(_, node)| node),
+        ))
+      }
+    }
+  }
+
+  fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+    visitor.visit_some(self)
+  }
+
+  fn deserialize_newtype_struct<V: Visitor<'de>>(
+    self,
+    name: &'static str,
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    match self {
+      Node::Values(_) => self.single()?.deserialize_newtype_struct(name, visitor),
+      map => visitor.visit_newtype_struct(map),
+    }
+  }
+
+  fn deserialize_enum<V: Visitor<'de>>(
+    self,
+    name: &'static str,
+    variants: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    self.single()?.deserialize_enum(name, variants, visitor)
+  }
+
+  fn deserialize_tuple<V: Visitor<'de>>(
+    self,
+    _: usize,
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    self.deserialize_seq(visitor)
+  }
+
+  fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+    match self {
+      Node::Map(map) => visitor.visit_map(de::value::MapDeserializer::new(map.into_iter())),
+      Node::Values(_) => Err(de::Error::invalid_type(
+        Unexpected::Other("value"),
+        &visitor,
+      )),
+    }
+  }
+
+  fn deserialize_struct<V: Visitor<'de>>(
+    self,
+    _: &'static str,
+    _: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    self.deserialize_map(visitor)
+  }
+
+  single! {
+    deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+    deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_f32
+    deserialize_f64 deserialize_char deserialize_str deserialize_string deserialize_bytes
+    deserialize_byte_buf deserialize_unit deserialize_identifier
+  }
+
+  forward_to_deserialize_any! {
+    i128 u128 unit_struct tuple_struct ignored_any
+  }
+}
+
+macro_rules! parse {
+  ($($method:ident => $visit:ident,)*) => {$(
+    fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+      let text = self.text(&visitor)?;
+      match text.parse() {
+        Ok(value) => visitor.$visit(value),
+        Err(_) => Err(de::Error::invalid_value(Unexpected::Str(&text), &visitor)),
+      }
+    }
+  )*};
+}
+
+impl Value {
+  fn text(self, visitor: &dyn de::Expected) -> Result<String, Error> {
+    match self {
+      Value::Text(text) => Ok(text),
+      Value::Upload(_) => Err(de::Error::invalid_type(Unexpected::Other("file"), visitor)),
+    }
+  }
+}
+
+impl<'de> Deserializer<'de> for Value {
+  type Error = Error;
+
+  fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+    let text = self.text(&visitor)?;
+    visitor.visit_string(text)
+  }
+
+  fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+    // checkboxes are sent as `on` by browsers
+    let text = self.text(&visitor)?;
+    match text.as_str() {
+      "true" | "on" | "1" => visitor.visit_bool(true),
+      "false" | "off" | "0" => visitor.visit_bool(false),
+      _ => Err(de::Error::invalid_value(Unexpected::Str(&text), &visitor)),
+    }
+  }
+
+  parse! {
+    deserialize_i8 => visit_i8,
+    deserialize_i16 => visit_i16,
+    deserialize_i32 => visit_i32,
+    deserialize_i64 => visit_i64,
+    deserialize_u8 => visit_u8,
+    deserialize_u16 => visit_u16,
+    deserialize_u32 => visit_u32,
+    deserialize_u64 => visit_u64,
+    deserialize_f32 => visit_f32,
+    deserialize_f64 => visit_f64,
+  }
+
+  fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+    visitor.visit_some(self)
+  }
+
+  fn deserialize_newtype_struct<V: Visitor<'de>>(
+    self,
+    name: &'static str,
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    match (self, name) {
+      (Value::Upload(index), UPLOAD) => visitor.visit_newtype_struct(index.into_deserializer()),
+      (Value::Text(text), UPLOAD) => Err(de::Error::invalid_type(Unexpected::Str(&text), &visitor)),
+      (value, _) => visitor.visit_newtype_struct(value),
+    }
+  }
+
+  fn deserialize_enum<V: Visitor<'de>>(
+    self,
+    _: &'static str,
+    _: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    let text = self.text(&visitor)?;
+    visitor.visit_enum(text.into_deserializer())
+  }
+
+  forward_to_deserialize_any! {
+    i128 u128 char str string bytes byte_buf unit unit_struct seq tuple tuple_struct
+    map struct identifier ignored_any
+  }
+}
@@ -0,0 +1,264 @@
+use crate::http::{header, HeaderName, HeaderValue};
+use headers::{Error, Header};
+use mime::Mime;
+use std::fmt;
+use std::str::FromStr;
+
+/// The `Accept` header, listing the media ranges the client accepts along
+/// with their relative [`Quality`].
+///
+/// ```rust
+/// use turbofish::http::headers::Accept;
+///
+/// # fn run(accept: Accept) {
+/// let available = [mime::APPLICATION_JSON, mime::TEXT_HTML];
+/// match accept.preferred(&available) {
+///   Some(mime) => println!("responding with {}", mime),
+///   None => println!("406 Not Acceptable"),
+/// }
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Accept(Vec<(Mime, Quality)>);
+
+impl Accept {
+  /// Creates an `Accept` header from media ranges and their quality.
+  pub fn new(ranges: impl IntoIterator<Item = (Mime, Quality)>) -> Self {
+    let mut ranges: Vec<_> = ranges.into_iter().collect();
+    // stable, so ranges of equal quality keep the client's order
+    ranges.sort_by(|(_, a), (_, b)| b.cmp(a));
+    Self(ranges)
+  }
+
+  /// Returns the media ranges, from most to least preferred.
+  pub fn iter(&self) -> impl Iterator<Item = (&Mime, Quality)> {
+    self.0.iter().map(|(mime, quality)| (mime, *quality))
+  }
+
+  /// Returns the quality of `mime`, given by the most specific range that
+  /// matches it, or zero if no range matches.
+  pub fn quality(&self, mime: &Mime) -> Quality {
+    self
+      .0
+      .iter()
+      .filter_map(|(range, quality)| {
+        specificity(range, mime).map(|specificity| (specificity, *quality))
+      })
+      .max_by_key(|(specificity, _)| *specificity)
+      .map_or(Quality::ZERO, |(_, quality)| quality)
+  }
+
+  /// Returns the media type in `available` with the highest quality. Ties
+  /// are broken by the order of `available`. Returns `None` if the client
+  /// accepts none of them.
+  pub fn preferred<'a>(&self, available: impl IntoIterator<Item = &'a Mime>) -> Option<&'a Mime> {
+    let mut best: Option<(&Mime, Quality)> = None;
+    for mime in available {
+      let quality = self.quality(mime);
+      if quality > Quality::ZERO && best.is_none_or(|(_, best)| quality > best) {
+        best = Some((mime, quality));
+      }
+    }
+    best.map(|(mime, _)| mime)
+  }
+}
+
+/// Returns how specific `range` is if it matches `mime`.
+fn specificity(range: &Mime, mime: &Mime) -> Option<usize> {
+  if range.type_() == mime::STAR {
+    return Some(0);
+  }
+  if range.type_() != mime.type_() {
+    return None;
+  }
+  if range.subtype() == mime::STAR {
+    return Some(1);
+  }
+  if range.subtype() != mime.subtype() {
+    return None;
+  }
+
+  let mut params = 0;
+  for (name, value) in range.params() {
+    match mime.get_param(name) {
+      Some(other) if other.as_str().eq_ignore_ascii_case(value.as_str()) => params += 1,
+      _ => return None,
+    }
+  }
+  Some(2 + params)
+}
+
+fn parse_range(range: &str) -> Option<(Mime, Quality)> {
+  let mut parts = range.split(';');
+  let mut media = parts.next()?.trim().to_owned();
+  let mut quality = Quality::ONE;
+
+  for param in parts {
+    let (name, value) = param.split_once('=')?;
+    // parameters after `q` are accept extensions, not part of the range
+    if name.trim().eq_ignore_ascii_case("q") {
+      quality = value.trim().parse().ok()?;
+      break;
+    }
+    media.push(';');
+    media.push_str(param);
+  }
+
+  Some((media.parse().ok()?, quality))
+}
+
+impl Header for Accept {
+  fn name() -> &'static HeaderName {
+    &header::ACCEPT
+  }
+
+  fn decode<'i, I>(values: &mut I) -> Result<Self, Error>
+  where
+    I: Iterator<Item = &'i HeaderValue>,
+  {
+    let mut ranges = Vec::new();
+    for value in values {
+      let value = value.to_str().map_err(|_| Error::invalid())?;
+      for range in value
+        .split(',')
+        .map(str::trim)
+        .filter(|range| !range.is_empty())
+      {
+        ranges.push(parse_range(range).ok_or_else(Error::invalid)?);
+      }
+    }
+    Ok(Self::new(ranges))
+  }
+
+  fn encode<E: Extend<HeaderValue>>(&self, values: &mut E) {
+    let value = self
+      .0
+      .iter()
+      .map(|(mime, quality)| match *quality {
+        Quality::ONE => mime.to_string(),
+        quality => format!("{};q={}", mime, quality),
+      })
+      .collect::<Vec<_>>()
+      .join(", ");
+
+    values.extend(std::iter::once(HeaderValue::from_str(&value).unwrap()));
+  }
+}
+
+/// The relative preference of a value in a header such as `Accept`, from
+/// zero (not acceptable) to one, with three decimal digits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Quality(u16);
+
+impl Quality {
+  pub const ZERO: Quality = Quality(0);
+  pub const ONE: Quality = Quality(1000);
+
+  /// Creates a quality from thousandths, returning `None` if `millis` is
+  /// greater than 1000.
+  pub fn from_millis(millis: u16) -> Option<Self> {
+    if millis <= 1000 {
+      Some(Self(millis))
+    } else {
+      None
+    }
+  }
+
+  pub fn as_millis(self) -> u16 {
+    self.0
+  }
+}
+
+impl Default for Quality {
+  fn default() -> Self {
+    Self::ONE
+  }
+}
+
+impl FromStr for Quality {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Error> {
+    let (int, fraction) = s.split_once('.').unwrap_or((s, ""));
+    if fraction.len() > 3 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+      return Err(Error::invalid());
+    }
+
+    let millis: u16 = format!("{:0<3}", fraction)
+      .parse()
+      .map_err(|_| Error::invalid())?;
+    match int {
+      "0" => Ok(Self(millis)),
+      "1" if millis == 0 => Ok(Self::ONE),
+      _ => Err(Error::invalid()),
+    }
+  }
+}
+
+impl fmt::Display for Quality {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.0 {
+      0 => f.write_str("0"),
+      1000 => f.write_str("1"),
+      millis => write!(f, "0.{}", format!("{:03}", millis).trim_end_matches('0')),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use headers::HeaderMapExt;
+  use http::HeaderMap;
+
+  #[test]
+  fn accept() {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+      header::ACCEPT,
+      HeaderValue::from_static("text/*;q=0.3, text/html;q=0.7, text/html;level=1, */*;q=0.5"),
+    );
+    let accept: Accept = headers.typed_get().unwrap();
+
+    let html: Mime = "text/html".parse().unwrap();
+    let level: Mime = "text/html;level=1".parse().unwrap();
+    assert_eq!(accept.quality(&level), Quality::ONE);
+    assert_eq!(accept.quality(&html), Quality::from_millis(700).unwrap());
+    assert_eq!(
+      accept.quality(&mime::TEXT_PLAIN),
+      Quality::from_millis(300).unwrap()
+    );
+    assert_eq!(
+      accept.quality(&mime::IMAGE_PNG),
+      Quality::from_millis(500).unwrap()
+    );
+    assert_eq!(
+      accept.preferred(&[mime::TEXT_PLAIN, html.clone()]),
+      Some(&html)
+    );
+
+    let mut headers = HeaderMap::new();
+    headers.typed_insert(accept);
+    assert_eq!(
+      headers[header::ACCEPT],
+      "text/html;level=1, text/html;q=0.7, */*;q=0.5, text/*;q=0.3"
+    );
+
+    for invalid in [
+      "text/html;q=1.5",
+      "text/html;q=0.1234",
+      "text/html;q=",
+      "text",
+    ] {
+      headers.insert(header::ACCEPT, HeaderValue::from_static(invalid));
+      assert!(headers.typed_try_get::<Accept>().is_err(), "{}", invalid);
+    }
+
+    headers.insert(
+      header::ACCEPT,
+      HeaderValue::from_static("application/json;q=0"),
+    );
+    let accept: Accept = headers.typed_get().unwrap();
+    assert_eq!(accept.preferred(&[mime::APPLICATION_JSON]), None);
+  }
+}
@@ -0,0 +1,285 @@
+use crate::http::{header, HeaderName, HeaderValue};
+use headers::{Error, Header};
+use std::fmt::Write;
+
+/// The `Forwarded` header, describing the proxies a request went through
+/// ([RFC 7239](https://tools.ietf.org/html/rfc7239)).
+///
+/// Each proxy appends an element, so the first element describes the
+/// client and the last one the closest proxy. The header is sent by
+/// clients as much as proxies, and should only be trusted as far as the
+/// proxies that set it are.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Forwarded(Vec<ForwardedElement>);
+
+impl Forwarded {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Appends an element, as a proxy forwarding the request would.
+  pub fn push(&mut self, element: ForwardedElement) {
+    self.0.push(element);
+  }
+
+  /// Returns the elements, from the client to the closest proxy.
+  pub fn iter(&self) -> impl Iterator<Item = &ForwardedElement> {
+    self.0.iter()
+  }
+}
+
+impl std::iter::FromIterator<ForwardedElement> for Forwarded {
+  fn from_iter<I: IntoIterator<Item = ForwardedElement>>(iter: I) -> Self {
+    Self(iter.into_iter().collect())
+  }
+}
+
+/// A single element of the [`Forwarded`] header, added by one proxy.
+///
+/// Values may contain any visible ASCII characters and spaces. The setters
+/// reject anything else, such as control characters that would allow
+/// injecting other headers.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ForwardedElement {
+  by: Option<String>,
+  forwarded_for: Option<String>,
+  host: Option<String>,
+  proto: Option<String>,
+}
+
+impl ForwardedElement {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Returns the interface where the request came into the proxy.
+  pub fn by(&self) -> Option<&str> {
+    self.by.as_deref()
+  }
+
+  /// Returns the node that made the request to the proxy, such as
+  /// `192.0.2.60` or `[2001:db8::1]:4711`.
+  pub fn forwarded_for(&self) -> Option<&str> {
+    self.forwarded_for.as_deref()
+  }
+
+  /// Returns the `Host` header of the request as received by the proxy.
+  pub fn host(&self) -> Option<&str> {
+    self.host.as_deref()
+  }
+
+  /// Returns the protocol used to make the request to the proxy.
+  pub fn proto(&self) -> Option<&str> {
+    self.proto.as_deref()
+  }
+
+  pub fn set_by(&mut self, by: impl Into<String>) -> Result<(), Error> {
+    self.by = Some(validate(by.into())?);
+    Ok(())
+  }
+
+  pub fn set_forwarded_for(&mut self, node: impl Into<String>) -> Result<(), Error> {
+    self.forwarded_for = Some(validate(node.into())?);
+    Ok(())
+  }
+
+  pub fn set_host(&mut self, host: impl Into<String>) -> Result<(), Error> {
+    self.host = Some(validate(host.into())?);
+    Ok(())
+  }
+
+  pub fn set_proto(&mut self, proto: impl Into<String>) -> Result<(), Error> {
+    self.proto = Some(validate(proto.into())?);
+    Ok(())
+  }
+
+  fn params(&self) -> impl Iterator<Item = (&'static str, &str)> {
+    let params = vec![
+      ("for", &self.forwarded_for),
+      ("by", &self.by),
+      ("host", &self.host),
+      ("proto", &self.proto),
+    ];
+    params
+      .into_iter()
+      .filter_map(|(name, value)| Some((name, value.as_deref()?)))
+  }
+
+  /// Sets a parameter parsed from a header, rejecting duplicates.
+  fn set(&mut self, name: &str, value: String) -> Result<(), Error> {
+    let slot = match name.to_ascii_lowercase().as_str() {
+      "by" => &mut self.by,
+      "for" => &mut self.forwarded_for,
+      "host" => &mut self.host,
+      "proto" => &mut self.proto,
+      // extensions
+      _ => return Ok(()),
+    };
+
+    if slot.is_some() {
+      return Err(Error::invalid());
+    }
+    *slot = Some(validate(value)?);
+    Ok(())
+  }
+}
+
+fn validate(value: String) -> Result<String, Error> {
+  if value
+    .bytes()
+    .all(|b| b == b'\t' || (b' '..=b'~').contains(&b))
+  {
+    Ok(value)
+  } else {
+    Err(Error::invalid())
+  }
+}
+
+fn is_token(value: &str) -> bool {
+  !value.is_empty()
+    && value
+      .bytes()
+      .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+fn parse(mut rest: &str, elements: &mut Vec<ForwardedElement>) -> Result<(), Error> {
+  let mut element = ForwardedElement::new();
+
+  loop {
+    let (name, value) = rest.split_once('=').ok_or_else(Error::invalid)?;
+    let name = name.trim();
+    if !is_token(name) {
+      return Err(Error::invalid());
+    }
+
+    let value = value.trim_start();
+    let value = match value.strip_prefix('"') {
+      Some(quoted) => {
+        let mut unquoted = String::new();
+        let mut chars = quoted.char_indices();
+        loop {
+          match chars.next().ok_or_else(Error::invalid)? {
+            (_, '\\') => unquoted.push(chars.next().ok_or_else(Error::invalid)?.1),
+            (i, '"') => {
+              rest = &quoted[i + 1..];
+              break;
+            }
+            (_, c) => unquoted.push(c),
+          }
+        }
+        unquoted
+      }
+      None => {
+        let end = value.find(&[';', ','][..]).unwrap_or(value.len());
+        let token = value[..end].trim();
+        if !is_token(token) {
+          return Err(Error::invalid());
+        }
+        rest = &value[end..];
+        token.to_owned()
+      }
+    };
+
+    element.set(name, value)?;
+
+    rest = rest.trim_start();
+    match rest.chars().next() {
+      None => {
+        elements.push(element);
+        return Ok(());
+      }
+      Some(';') => {}
+      Some(',') => elements.push(std::mem::take(&mut element)),
+      Some(_) => return Err(Error::invalid()),
+    }
+    rest = &rest[1..];
+  }
+}
+
+impl Header for Forwarded {
+  fn name() -> &'static HeaderName {
+    &header::FORWARDED
+  }
+
+  fn decode<'i, I>(values: &mut I) -> Result<Self, Error>
+  where
+    I: Iterator<Item = &'i HeaderValue>,
+  {
+    let mut elements = Vec::new();
+    for value in values {
+      parse(value.to_str().map_err(|_| Error::invalid())?, &mut elements)?;
+    }
+    Ok(Self(elements))
+  }
+
+  fn encode<E: Extend<HeaderValue>>(&self, values: &mut E) {
+    let mut value = String::new();
+
+    for element in &self.0 {
+      let mut params = element.params().peekable();
+      if params.peek().is_none() {
+        continue;
+      }
+      if !value.is_empty() {
+        value.push_str(", ");
+      }
+
+      for (i, (name, param)) in params.enumerate() {
+        if i > 0 {
+          value.push(';');
+        }
+        if is_token(param) {
+          let _ = write!(value, "{}={}", name, param);
+        } else {
+          let escaped = param.replace('\\', "\\\\").replace('"', "\\\"");
+          let _ = write!(value, "{}=\"{}\"", name, escaped);
+        }
+      }
+    }
+
+    // values are validated when set
+    values.extend(std::iter::once(HeaderValue::from_str(&value).unwrap()));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use headers::HeaderMapExt;
+  use http::HeaderMap;
+
+  #[test]
+  fn forwarded() {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+      header::FORWARDED,
+      HeaderValue::from_static(
+        r#"for=192.0.2.60;proto=http;by=203.0.113.43, For="[2001:db8::1]:4711""#,
+      ),
+    );
+    let forwarded: Forwarded = headers.typed_get().unwrap();
+
+    let elements: Vec<_> = forwarded.iter().collect();
+    assert_eq!(elements.len(), 2);
+    assert_eq!(elements[0].forwarded_for(), Some("192.0.2.60"));
+    assert_eq!(elements[0].proto(), Some("http"));
+    assert_eq!(elements[0].by(), Some("203.0.113.43"));
+    assert_eq!(elements[1].forwarded_for(), Some("[2001:db8::1]:4711"));
+
+    let mut headers = HeaderMap::new();
+    headers.typed_insert(forwarded);
+    assert_eq!(
+      headers[header::FORWARDED],
+      r#"for=192.0.2.60;by=203.0.113.43;proto=http, for="[2001:db8::1]:4711""#
+    );
+
+    for invalid in ["for=a;for=b", "for=\"unterminated", "for", "for=a b"] {
+      headers.insert(header::FORWARDED, HeaderValue::from_static(invalid));
+      assert!(headers.typed_try_get::<Forwarded>().is_err(), "{}", invalid);
+    }
+
+    let mut element = ForwardedElement::new();
+    assert!(element.set_host("example.com\r\nx-injected: 1").is_err());
+    assert!(element.set_host("example.com").is_ok());
+  }
+}
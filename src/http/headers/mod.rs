@@ -0,0 +1,39 @@
+//! Typed headers.
+//!
+//! Typed headers are read with
+//! [`Request::typed_header`](crate::http::Request::typed_header) and set
+//! with [`Response::set_typed_header`](crate::http::Response::set_typed_header)
+//! or [`ResponseBuilder::typed_header`](crate::http::ResponseBuilder::typed_header).
+//! Along with the headers of the [`headers`](::headers) crate, this module
+//! provides [`Accept`] and [`Forwarded`].
+//!
+//! ```rust
+//! use turbofish::http::headers::authorization::Bearer;
+//! use turbofish::http::headers::{Authorization, ContentType};
+//! use turbofish::http::{Body, Request, Response, StatusCode};
+//!
+//! async fn upload(req: Request) -> Response {
+//!   let token = match req.typed_header::<Authorization<Bearer>>() {
+//!     Some(Authorization(bearer)) => bearer.token().to_owned(),
+//!     None => return Response::builder().status(StatusCode::UNAUTHORIZED).body(Body::empty()).unwrap(),
+//!   };
+//!
+//!   if req.typed_header::<ContentType>() != Some(ContentType::octet_stream()) {
+//!     return Response::builder().status(StatusCode::UNSUPPORTED_MEDIA_TYPE).body(Body::empty()).unwrap();
+//!   }
+//!
+//!   Response::text(format!("uploaded with {}", token))
+//! }
+//! ```
+
+mod accept;
+mod forwarded;
+
+#[doc(no_inline)]
+pub use ::headers::*;
+
+#[doc(inline)]
+pub use accept::{Accept, Quality};
+
+#[doc(inline)]
+pub use forwarded::{Forwarded, ForwardedElement};
@@ -0,0 +1,342 @@
+use crate::config::Config;
+use crate::http::{header, Body, HeaderMap, StatusCode};
+use bytes::Bytes;
+use futures::{Stream, TryStreamExt};
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::{Instant, Sleep};
+
+/// An error returned while reading a request body that violates the
+/// limits set in the [`Config`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BodyLimitError {
+  /// The body is larger than the maximum body size.
+  TooLarge,
+  /// No data was received within the read timeout.
+  TimedOut,
+  /// Data was received slower than the minimum data rate.
+  TooSlow,
+}
+
+impl BodyLimitError {
+  /// Returns the status code the request is answered with.
+  pub fn status(&self) -> StatusCode {
+    match self {
+      BodyLimitError::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+      BodyLimitError::TimedOut | BodyLimitError::TooSlow => StatusCode::REQUEST_TIMEOUT,
+    }
+  }
+}
+
+impl fmt::Display for BodyLimitError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      BodyLimitError::TooLarge => f.write_str("request body is too large"),
+      BodyLimitError::TimedOut => f.write_str("timed out reading request body"),
+      BodyLimitError::TooSlow => f.write_str("request body was sent too slowly"),
+    }
+  }
+}
+
+impl Error for BodyLimitError {}
+
+/// The limits applied to a request body.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct BodyLimits {
+  pub max_size: Option<u64>,
+  pub read_timeout: Option<Duration>,
+  pub min_rate: Option<(u64, Duration)>,
+}
+
+impl BodyLimits {
+  pub fn new(config: &Config) -> Self {
+    Self {
+      max_size: config.body_limit,
+      read_timeout: config.body_read_timeout,
+      min_rate: config.body_min_rate,
+    }
+  }
+
+  /// Wraps the body so that reading it fails once a limit is violated.
+  pub fn apply(self, body: Body) -> (Body, BodyGuard) {
+    let guard = BodyGuard {
+      max_size: self.max_size,
+      violation: Arc::default(),
+    };
+
+    let body = match body {
+      Body::Empty => Body::Empty,
+      Body::Once(bytes) => match self.max_size {
+        Some(max) if bytes.len() as u64 > max => {
+          let stream = futures::stream::iter(Some(Ok::<_, BoxError>(bytes)));
+          Body::Streamed(Box::pin(Limited::new(stream, self, &guard)))
+        }
+        _ => Body::Once(bytes),
+      },
+      Body::Streamed(stream) => Body::Streamed(Box::pin(Limited::new(stream, self, &guard))),
+      body @ Body::Channel(_) => {
+        let stream = body.map_err(BoxError::from);
+        Body::Streamed(Box::pin(Limited::new(stream, self, &guard)))
+      }
+    };
+
+    (body, guard)
+  }
+}
+
+/// Records limit violations of a request body, so that the request can be
+/// answered with the appropriate status even if the handler ignores the
+/// error.
+#[derive(Clone)]
+pub(crate) struct BodyGuard {
+  max_size: Option<u64>,
+  violation: Arc<Mutex<Option<BodyLimitError>>>,
+}
+
+impl BodyGuard {
+  /// Checks the `Content-Length` header against the maximum body size.
+  pub fn check(&self, headers: &HeaderMap) -> Result<(), BodyLimitError> {
+    let len = headers
+      .get(header::CONTENT_LENGTH)
+      .and_then(|len| len.to_str().ok())
+      .and_then(|len| len.parse::<u64>().ok());
+
+    match (len, self.max_size) {
+      (Some(len), Some(max)) if len > max => Err(BodyLimitError::TooLarge),
+      _ => Ok(()),
+    }
+  }
+
+  /// Returns the first limit the body violated while being read.
+  pub fn violation(&self) -> Option<BodyLimitError> {
+    *self.violation.lock().unwrap()
+  }
+
+  fn violate(&self, err: BodyLimitError) -> BodyLimitError {
+    self.violation.lock().unwrap().get_or_insert(err);
+    err
+  }
+}
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+struct Limited<S> {
+  inner: S,
+  limits: BodyLimits,
+  guard: BodyGuard,
+  read: u64,
+  // the clocks start on the first read, not when the request is routed
+  started: Option<Instant>,
+  timeout: Option<Pin<Box<Sleep>>>,
+  // fires when the body falls behind the minimum rate, so that a client
+  // that stops sending is caught without waiting for its next chunk
+  rate_timer: Option<Pin<Box<Sleep>>>,
+  done: bool,
+}
+
+impl<S> Limited<S> {
+  fn new(inner: S, limits: BodyLimits, guard: &BodyGuard) -> Self {
+    Self {
+      inner,
+      limits,
+      guard: guard.clone(),
+      read: 0,
+      started: None,
+      timeout: None,
+      rate_timer: None,
+      done: false,
+    }
+  }
+
+  fn start(&mut self) {
+    self.started = Some(Instant::now());
+    self.timeout = self
+      .limits
+      .read_timeout
+      .map(|t| Box::pin(tokio::time::sleep(t)));
+    self.rate_timer = self
+      .rate_deadline()
+      .map(|deadline| Box::pin(tokio::time::sleep_until(deadline)));
+  }
+
+  /// Returns the time at which the bytes read so far fall below the
+  /// minimum rate.
+  fn rate_deadline(&self) -> Option<Instant> {
+    match (self.limits.min_rate, self.started) {
+      (Some((rate, grace)), Some(started)) if rate > 0 => {
+        let allowed = Duration::from_secs_f64(self.read as f64 / rate as f64);
+        Some(started + allowed.max(grace))
+      }
+      _ => None,
+    }
+  }
+
+  fn fail(&mut self, err: BodyLimitError) -> Poll<Option<Result<Bytes, BoxError>>> {
+    self.done = true;
+    Poll::Ready(Some(Err(self.guard.violate(err).into())))
+  }
+}
+
+impl<S> Stream for Limited<S>
+where
+  S: Stream<Item = Result<Bytes, BoxError>> + Unpin,
+{
+  type Item = Result<Bytes, BoxError>;
+
+  fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    if self.done {
+      return Poll::Ready(None);
+    }
+
+    if self.started.is_none() {
+      self.start();
+    }
+
+    let chunk = match Pin::new(&mut self.inner).poll_next(cx) {
+      Poll::Ready(Some(Ok(chunk))) => chunk,
+      Poll::Ready(other) => return Poll::Ready(other),
+      Poll::Pending => {
+        if let Some(timeout) = &mut self.timeout {
+          if timeout.as_mut().poll(cx).is_ready() {
+            return self.fail(BodyLimitError::TimedOut);
+          }
+        }
+        if let Some(rate_timer) = &mut self.rate_timer {
+          if rate_timer.as_mut().poll(cx).is_ready() {
+            return self.fail(BodyLimitError::TooSlow);
+          }
+        }
+        return Poll::Pending;
+      }
+    };
+
+    self.read += chunk.len() as u64;
+
+    if let Some(max) = self.limits.max_size {
+      if self.read > max {
+        return self.fail(BodyLimitError::TooLarge);
+      }
+    }
+
+    if let (Some((rate, grace)), Some(started)) = (self.limits.min_rate, self.started) {
+      let elapsed = started.elapsed();
+      if elapsed > grace && (self.read as f64) < rate as f64 * elapsed.as_secs_f64() {
+        return self.fail(BodyLimitError::TooSlow);
+      }
+    }
+
+    if let Some(read_timeout) = self.limits.read_timeout {
+      if let Some(timeout) = &mut self.timeout {
+        timeout.as_mut().reset(Instant::now() + read_timeout);
+      }
+    }
+
+    if let Some(deadline) = self.rate_deadline() {
+      if let Some(rate_timer) = &mut self.rate_timer {
+        rate_timer.as_mut().reset(deadline);
+      }
+    }
+
+    Poll::Ready(Some(Ok(chunk)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use futures::StreamExt;
+
+  fn chunks(chunks: Vec<&'static str>) -> Body {
+    Body::Streamed(Box::pin(futures::stream::iter(
+      chunks.into_iter().map(|c| Ok(Bytes::from(c))),
+    )))
+  }
+
+  async fn read(body: Body) -> Result<Vec<Bytes>, BoxError> {
+    match body {
+      Body::Empty => Ok(Vec::new()),
+      Body::Once(bytes) => Ok(vec![bytes]),
+      Body::Streamed(stream) => stream.try_collect().await,
+      body @ Body::Channel(_) => body.map_err(Into::into).try_collect().await,
+    }
+  }
+
+  #[tokio::test]
+  async fn max_size() {
+    let limits = BodyLimits {
+      max_size: Some(8),
+      ..BodyLimits::default()
+    };
+
+    let (body, guard) = limits.apply(chunks(vec!["hello", "foo"]));
+    assert_eq!(read(body).await.unwrap().len(), 2);
+    assert_eq!(guard.violation(), None);
+
+    let (body, guard) = limits.apply(chunks(vec!["hello", "world"]));
+    let err = read(body).await.unwrap_err();
+    assert_eq!(err.downcast_ref(), Some(&BodyLimitError::TooLarge));
+    assert_eq!(guard.violation(), Some(BodyLimitError::TooLarge));
+
+    let (body, guard) = limits.apply(Body::Once(Bytes::from("hello world")));
+    assert!(read(body).await.is_err());
+    assert_eq!(guard.violation(), Some(BodyLimitError::TooLarge));
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_LENGTH, 9.into());
+    assert_eq!(guard.check(&headers), Err(BodyLimitError::TooLarge));
+  }
+
+  #[tokio::test]
+  async fn read_timeout() {
+    let limits = BodyLimits {
+      read_timeout: Some(Duration::from_millis(10)),
+      ..BodyLimits::default()
+    };
+
+    let stream =
+      futures::stream::iter(vec![Ok(Bytes::from("hello"))]).chain(futures::stream::pending());
+    let (body, guard) = limits.apply(Body::Streamed(Box::pin(stream)));
+    assert!(read(body).await.is_err());
+    assert_eq!(guard.violation(), Some(BodyLimitError::TimedOut));
+  }
+
+  #[tokio::test]
+  async fn min_rate() {
+    let limits = BodyLimits {
+      min_rate: Some((100, Duration::from_millis(10))),
+      ..BodyLimits::default()
+    };
+
+    let (body, guard) = limits.apply(chunks(vec!["hello", "world"]));
+    assert_eq!(read(body).await.unwrap().len(), 2);
+    assert_eq!(guard.violation(), None);
+
+    // the client stops sending, so no chunk arrives to check the rate on
+    let stream =
+      futures::stream::iter(vec![Ok(Bytes::from("hello"))]).chain(futures::stream::pending());
+    let (body, guard) = limits.apply(Body::Streamed(Box::pin(stream)));
+    let read = tokio::time::timeout(Duration::from_secs(5), read(body)).await;
+    assert!(read.expect("slow body was not rejected").is_err());
+    assert_eq!(guard.violation(), Some(BodyLimitError::TooSlow));
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn idle_before_first_read() {
+    let limits = BodyLimits {
+      read_timeout: Some(Duration::from_millis(10)),
+      min_rate: Some((1000, Duration::from_millis(10))),
+      ..BodyLimits::default()
+    };
+
+    // a handler that does other work before reading the body
+    let (body, guard) = limits.apply(chunks(vec!["hello", "world"]));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(read(body).await.unwrap().len(), 2);
+    assert_eq!(guard.violation(), None);
+  }
+}
@@ -2,18 +2,48 @@ mod body;
 mod response;
 mod request;
 mod cookies;
+mod form;
+pub mod headers;
+mod limit;
+mod multipart;
+mod negotiate;
+mod query;
+mod sse;
 
 #[doc(inline)]
 pub use request::Request;
 
 #[doc(inline)]
-pub use cookies::CookieJar;
+pub use cookies::{CookieJar, PrivateJar, SignedJar};
+
+#[doc(no_inline)]
+pub use cookie::{Cookie, Key, SameSite};
+
+#[doc(inline)]
+pub use body::{Body, BodyError, BodySender, ChannelBody, Disconnected};
 
 #[doc(inline)]
-pub use body::Body;
+pub use limit::BodyLimitError;
 
 #[doc(inline)]
-pub use response::Response;
+pub use multipart::{Field, Multipart, MultipartError, UploadedFile};
+
+pub(crate) use limit::{BodyGuard, BodyLimits};
+
+#[doc(inline)]
+pub use negotiate::{Cbor, Csv, Format, Json, MessagePack, Negotiated};
+
+#[doc(inline)]
+pub use query::QueryError;
+
+#[doc(inline)]
+pub use response::{Builder as ResponseBuilder, Response};
+
+#[doc(inline)]
+pub use sse::{Event, Sse};
+
+#[doc(no_inline)]
+pub use self::headers::{Header, HeaderMapExt};
 
 #[doc(inline)]
-pub use http::{HeaderMap, HeaderValue, Method, StatusCode, Uri};
+pub use http::{header, Extensions, HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri, Version};
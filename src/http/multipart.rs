@@ -0,0 +1,529 @@
+use crate::http::form::{Fields, Value, UPLOAD};
+use crate::http::{Body, BodyError, HeaderMap, StatusCode};
+use bytes::{Bytes, BytesMut};
+use mime::Mime;
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use tempfile::TempPath;
+use tokio::io::AsyncWriteExt;
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// A streaming `multipart/form-data` parser, created with
+/// [`Request::multipart`](crate::http::Request::multipart).
+///
+/// Fields are parsed as they arrive, so large uploads never have to be
+/// buffered in memory:
+///
+/// ```rust
+/// use turbofish::http::{MultipartError, Request, Response};
+///
+/// async fn save(req: &mut Request) -> Result<(), MultipartError> {
+///   let mut form = req.multipart()?.field_limit(100 * 1024 * 1024);
+///
+///   while let Some(field) = form.next_field().await? {
+///     if field.file_name().is_some() {
+///       let file = field.save_temp().await?;
+///       file.persist("uploads/latest")?;
+///     }
+///   }
+///
+///   Ok(())
+/// }
+///
+/// async fn upload(mut req: Request) -> Response {
+///   match save(&mut req).await {
+///     Ok(()) => Response::no_content(),
+///     Err(err) => Response::builder().status(err.status()).body(err.to_string()).unwrap(),
+///   }
+/// }
+/// ```
+///
+/// Forms can also be deserialized into a struct with
+/// [`deserialize`](Multipart::deserialize), with file fields saved to
+/// temporary files.
+pub struct Multipart {
+  body: Option<Body>,
+  boundary: String,
+  field_limit: Option<u64>,
+  total_limit: Option<u64>,
+  inner: Option<multer::Multipart<'static>>,
+}
+
+impl Multipart {
+  pub(crate) fn new(body: Body, boundary: String) -> Self {
+    Self {
+      body: Some(body),
+      boundary,
+      field_limit: None,
+      total_limit: None,
+      inner: None,
+    }
+  }
+
+  /// Sets the maximum size of a single field in bytes (default is no
+  /// limit).
+  pub fn field_limit(mut self, bytes: impl Into<Option<u64>>) -> Self {
+    self.field_limit = bytes.into();
+    self
+  }
+
+  /// Sets the maximum size of the whole form in bytes (default is no limit
+  /// beyond the request body limit).
+  pub fn total_limit(mut self, bytes: impl Into<Option<u64>>) -> Self {
+    self.total_limit = bytes.into();
+    self
+  }
+
+  /// Returns the next field of the form, or `None` once all fields have
+  /// been read. The previous field must be dropped before calling this.
+  pub async fn next_field(&mut self) -> Result<Option<Field>, MultipartError> {
+    if self.inner.is_none() {
+      let mut limit = multer::SizeLimit::new();
+      if let Some(field_limit) = self.field_limit {
+        limit = limit.per_field(field_limit);
+      }
+      if let Some(total_limit) = self.total_limit {
+        limit = limit.whole_stream(total_limit);
+      }
+
+      let body = self.body.take().unwrap_or_default();
+      let constraints = multer::Constraints::new().size_limit(limit);
+      self.inner = Some(multer::Multipart::with_constraints(
+        body,
+        &self.boundary,
+        constraints,
+      ));
+    }
+
+    let inner = self.inner.as_mut().unwrap();
+    let field = inner.next_field().await.map_err(MultipartError::from)?;
+    Ok(field.map(|inner| Field { inner }))
+  }
+
+  /// Reads the whole form and deserializes it into `T`.
+  ///
+  /// Fields are deserialized like query strings (see
+  /// [`Request::query`](crate::http::Request::query)), so repeated fields
+  /// and bracket notation are supported. Fields with a file name are saved
+  /// to temporary files and deserialized into [`UploadedFile`].
+  ///
+  /// ```rust
+  /// use serde::Deserialize;
+  /// use turbofish::http::UploadedFile;
+  ///
+  /// #[derive(Deserialize)]
+  /// struct Post {
+  ///   title: String,
+  ///   tags: Vec<String>,
+  ///   cover: Option<UploadedFile>,
+  ///   #[serde(default)]
+  ///   attachments: Vec<UploadedFile>,
+  /// }
+  /// ```
+  pub async fn deserialize<T: DeserializeOwned>(mut self) -> Result<T, MultipartError> {
+    let mut fields = Fields::default();
+    let mut uploads = Vec::new();
+
+    while let Some(field) = self.next_field().await? {
+      let name = match field.name() {
+        Some(name) => name.to_owned(),
+        None => continue,
+      };
+
+      let value = if field.file_name().is_some() {
+        uploads.push(Some(field.save_temp().await?));
+        Value::Upload(uploads.len() - 1)
+      } else {
+        Value::Text(field.text().await?)
+      };
+
+      fields
+        .insert(&name, value)
+        .map_err(|err| MultipartError::Deserialize(err.to_string()))?;
+    }
+
+    let _uploads = Uploads::set(uploads);
+    T::deserialize(fields.into_deserializer())
+      .map_err(|err| MultipartError::Deserialize(err.to_string()))
+  }
+}
+
+/// A field of a multipart form.
+pub struct Field {
+  inner: multer::Field<'static>,
+}
+
+impl Field {
+  /// Returns the name of the field.
+  pub fn name(&self) -> Option<&str> {
+    self.inner.name()
+  }
+
+  /// Returns the file name sent by the client, if this field is a file.
+  /// The name is not sanitized and must not be used as a path as is.
+  pub fn file_name(&self) -> Option<&str> {
+    self.inner.file_name()
+  }
+
+  /// Returns the content type of the field.
+  pub fn content_type(&self) -> Option<&Mime> {
+    self.inner.content_type()
+  }
+
+  /// Returns the headers of the field.
+  pub fn headers(&self) -> &HeaderMap {
+    self.inner.headers()
+  }
+
+  /// Returns the next chunk of the field, or `None` once the field has been
+  /// read completely.
+  pub async fn chunk(&mut self) -> Result<Option<Bytes>, MultipartError> {
+    self.inner.chunk().await.map_err(MultipartError::from)
+  }
+
+  /// Reads the whole field into memory.
+  pub async fn bytes(mut self) -> Result<Bytes, MultipartError> {
+    let mut bytes = BytesMut::new();
+    while let Some(chunk) = self.chunk().await? {
+      bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes.freeze())
+  }
+
+  /// Reads the field as text, decoded with the charset of its content
+  /// type (default is UTF-8).
+  pub async fn text(self) -> Result<String, MultipartError> {
+    self.inner.text().await.map_err(MultipartError::from)
+  }
+
+  /// Streams the field into a file at `path`, returning the number of
+  /// bytes written.
+  pub async fn save(self, path: impl AsRef<Path>) -> Result<u64, MultipartError> {
+    let file = tokio::fs::File::create(path)
+      .await
+      .map_err(MultipartError::Io)?;
+    self.write_to(file).await
+  }
+
+  /// Streams the field into a temporary file, which is deleted when the
+  /// returned [`UploadedFile`] is dropped unless it is persisted.
+  pub async fn save_temp(self) -> Result<UploadedFile, MultipartError> {
+    let file_name = self.file_name().map(str::to_owned);
+    let content_type = self.content_type().cloned();
+
+    let (file, path) = tempfile::NamedTempFile::new()
+      .map_err(MultipartError::Io)?
+      .into_parts();
+    let size = self.write_to(tokio::fs::File::from_std(file)).await?;
+
+    Ok(UploadedFile {
+      file_name,
+      content_type,
+      path,
+      size,
+    })
+  }
+
+  async fn write_to(mut self, mut file: tokio::fs::File) -> Result<u64, MultipartError> {
+    let mut written = 0;
+    while let Some(chunk) = self.chunk().await? {
+      file.write_all(&chunk).await.map_err(MultipartError::Io)?;
+      written += chunk.len() as u64;
+    }
+    file.flush().await.map_err(MultipartError::Io)?;
+    Ok(written)
+  }
+}
+
+/// A file uploaded in a multipart form, saved to a temporary file.
+///
+/// The file is deleted when this is dropped, unless it is moved somewhere
+/// else with [`persist`](UploadedFile::persist).
+#[derive(Debug)]
+pub struct UploadedFile {
+  file_name: Option<String>,
+  content_type: Option<Mime>,
+  path: TempPath,
+  size: u64,
+}
+
+impl UploadedFile {
+  /// Returns the file name sent by the client. The name is not sanitized
+  /// and must not be used as a path as is.
+  pub fn file_name(&self) -> Option<&str> {
+    self.file_name.as_deref()
+  }
+
+  /// Returns the content type sent by the client.
+  pub fn content_type(&self) -> Option<&Mime> {
+    self.content_type.as_ref()
+  }
+
+  /// Returns the path of the temporary file.
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  /// Returns the size of the file in bytes.
+  pub fn size(&self) -> u64 {
+    self.size
+  }
+
+  /// Moves the file to `path`, so that it is kept after this is dropped.
+  pub fn persist(self, path: impl AsRef<Path>) -> io::Result<()> {
+    self.path.persist(path).map_err(|err| err.error)
+  }
+}
+
+thread_local! {
+  static UPLOADS: RefCell<Vec<Option<UploadedFile>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The files of the form being deserialized on this thread, removed (and
+/// deleted unless taken) when dropped.
+struct Uploads;
+
+impl Uploads {
+  fn set(uploads: Vec<Option<UploadedFile>>) -> Self {
+    UPLOADS.with(|current| *current.borrow_mut() = uploads);
+    Uploads
+  }
+}
+
+impl Drop for Uploads {
+  fn drop(&mut self) {
+    let uploads = UPLOADS.with(|current| std::mem::take(&mut *current.borrow_mut()));
+    drop(uploads);
+  }
+}
+
+impl<'de> Deserialize<'de> for UploadedFile {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    struct UploadVisitor;
+
+    impl<'de> Visitor<'de> for UploadVisitor {
+      type Value = UploadedFile;
+
+      fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an uploaded file")
+      }
+
+      fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+      ) -> Result<Self::Value, D::Error> {
+        let index = usize::deserialize(deserializer)?;
+        UPLOADS
+          .with(|uploads| uploads.borrow_mut().get_mut(index).and_then(Option::take))
+          .ok_or_else(|| {
+            de::Error::custom("an uploaded file can only be deserialized from a multipart form")
+          })
+      }
+    }
+
+    deserializer.deserialize_newtype_struct(UPLOAD, UploadVisitor)
+  }
+}
+
+/// An error returned while reading a multipart form.
+#[derive(Debug)]
+pub enum MultipartError {
+  /// Reading the request body failed.
+  Body(BodyError),
+  /// A field is larger than the field limit.
+  FieldTooLarge(Option<String>),
+  /// The form is larger than the total limit.
+  TooLarge,
+  /// The body is not a valid multipart form.
+  Malformed(BoxError),
+  /// Saving a field to a file failed.
+  Io(io::Error),
+  /// The form could not be deserialized into the expected type.
+  Deserialize(String),
+}
+
+impl MultipartError {
+  /// Returns the status code the request should be answered with.
+  pub fn status(&self) -> StatusCode {
+    match self {
+      MultipartError::Body(err) => err.status(),
+      MultipartError::FieldTooLarge(_) | MultipartError::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+      MultipartError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+      MultipartError::Malformed(_) | MultipartError::Deserialize(_) => StatusCode::BAD_REQUEST,
+    }
+  }
+}
+
+impl From<BodyError> for MultipartError {
+  fn from(err: BodyError) -> Self {
+    MultipartError::Body(err)
+  }
+}
+
+impl From<io::Error> for MultipartError {
+  fn from(err: io::Error) -> Self {
+    MultipartError::Io(err)
+  }
+}
+
+impl From<multer::Error> for MultipartError {
+  fn from(err: multer::Error) -> Self {
+    match err {
+      multer::Error::FieldSizeExceeded { field_name, .. } => {
+        MultipartError::FieldTooLarge(field_name)
+      }
+      multer::Error::StreamSizeExceeded { .. } => MultipartError::TooLarge,
+      multer::Error::StreamReadFailed(err) => match err.downcast::<BodyError>() {
+        Ok(err) => MultipartError::Body(*err),
+        Err(err) => MultipartError::Malformed(err),
+      },
+      err => MultipartError::Malformed(err.into()),
+    }
+  }
+}
+
+impl fmt::Display for MultipartError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      MultipartError::Body(err) => err.fmt(f),
+      MultipartError::FieldTooLarge(Some(name)) => write!(f, "field `{}` is too large", name),
+      MultipartError::FieldTooLarge(None) => f.write_str("field is too large"),
+      MultipartError::TooLarge => f.write_str("multipart form is too large"),
+      MultipartError::Malformed(err) => write!(f, "invalid multipart form: {}", err),
+      MultipartError::Io(err) => write!(f, "failed to save upload: {}", err),
+      MultipartError::Deserialize(err) => write!(f, "invalid form: {}", err),
+    }
+  }
+}
+
+impl Error for MultipartError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match self {
+      MultipartError::Body(err) => Some(err),
+      MultipartError::Malformed(err) => Some(&**err),
+      MultipartError::Io(err) => Some(err),
+      _ => None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::http::Request;
+
+  const BODY: &str = "--X\r\n\
+    Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+    Hello\r\n\
+    --X\r\n\
+    Content-Disposition: form-data; name=\"tags\"\r\n\r\n\
+    a\r\n\
+    --X\r\n\
+    Content-Disposition: form-data; name=\"tags\"\r\n\r\n\
+    b\r\n\
+    --X\r\n\
+    Content-Disposition: form-data; name=\"draft\"\r\n\r\n\
+    on\r\n\
+    --X\r\n\
+    Content-Disposition: form-data; name=\"cover\"; filename=\"cover.png\"\r\n\
+    Content-Type: image/png\r\n\r\n\
+    not really a png\r\n\
+    --X--\r\n";
+
+  fn request() -> Request {
+    let chunks: Vec<Result<_, io::Error>> =
+      BODY.as_bytes().chunks(7).map(|c| Ok(c.to_vec())).collect();
+    hyper::Request::post("/")
+      .header("content-type", "multipart/form-data; boundary=X")
+      .body(hyper::Body::wrap_stream(futures::stream::iter(chunks)))
+      .unwrap()
+      .into()
+  }
+
+  #[derive(Deserialize)]
+  struct Post {
+    title: String,
+    tags: Vec<String>,
+    draft: bool,
+    cover: Option<UploadedFile>,
+    #[serde(default)]
+    attachments: Vec<UploadedFile>,
+  }
+
+  #[tokio::test]
+  async fn fields() {
+    let mut form = request().multipart().unwrap();
+
+    let field = form.next_field().await.unwrap().unwrap();
+    assert_eq!(field.name(), Some("title"));
+    assert_eq!(field.text().await.unwrap(), "Hello");
+
+    let mut cover = None;
+    while let Some(field) = form.next_field().await.unwrap() {
+      if field.file_name().is_some() {
+        cover = Some(field.save_temp().await.unwrap());
+      }
+    }
+
+    let cover = cover.unwrap();
+    assert_eq!(cover.file_name(), Some("cover.png"));
+    assert_eq!(cover.content_type(), Some(&mime::IMAGE_PNG));
+    assert_eq!(std::fs::read(cover.path()).unwrap(), b"not really a png");
+
+    let mut form = request().multipart().unwrap().field_limit(8);
+    let err = loop {
+      match form.next_field().await {
+        Ok(Some(field)) => {
+          if let Err(err) = field.bytes().await {
+            break err;
+          }
+        }
+        other => panic!("expected an error, got {:?}", other.map(|f| f.is_some())),
+      }
+    };
+    assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let mut req = Request::from(hyper::Request::new(hyper::Body::empty()));
+    assert_eq!(
+      req.multipart().err().unwrap().status(),
+      StatusCode::UNSUPPORTED_MEDIA_TYPE
+    );
+  }
+
+  #[tokio::test]
+  async fn deserialize() {
+    let post: Post = request().multipart().unwrap().deserialize().await.unwrap();
+    assert_eq!(post.title, "Hello");
+    assert_eq!(post.tags, vec!["a", "b"]);
+    assert!(post.draft);
+    assert!(post.attachments.is_empty());
+
+    let cover = post.cover.unwrap();
+    assert_eq!(cover.size(), 16);
+    let path = cover.path().to_owned();
+    assert!(path.exists());
+    drop(cover);
+    assert!(!path.exists());
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Spoofed {
+      title: UploadedFile,
+    }
+
+    let err = request()
+      .multipart()
+      .unwrap()
+      .deserialize::<Spoofed>()
+      .await
+      .unwrap_err();
+    assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+    assert!(serde_json::from_str::<UploadedFile>("0").is_err());
+  }
+}
@@ -0,0 +1,388 @@
+use crate::http::headers::Accept;
+use crate::http::{header, Body, HeaderValue, Request, Response, StatusCode};
+use mime::Mime;
+use serde::ser::{self, Impossible};
+use serde::Serialize;
+use std::error::Error;
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+type SerializeFn<T> = Box<dyn Fn(&T) -> Result<Vec<u8>, BoxError> + Send + Sync>;
+
+/// A response serialized into the format the client prefers, according
+/// to the request's `Accept` header.
+///
+/// [`Negotiated::new`] offers JSON, MessagePack, CBOR and CSV, in that
+/// order of preference when the client accepts several equally. More
+/// formats can be added by implementing [`Format`].
+///
+/// The response carries `Vary: Accept`. If the client accepts none of the
+/// formats, a `406 Not Acceptable` listing the available media types is
+/// returned instead.
+///
+/// ```rust
+/// use serde::Serialize;
+/// use turbofish::http::{Negotiated, Request, Response};
+///
+/// #[derive(Serialize)]
+/// struct Post {
+///   id: u64,
+///   title: String,
+/// }
+///
+/// async fn posts(req: Request) -> Response {
+///   let posts = vec![Post { id: 1, title: "Hello".into() }];
+///   Negotiated::new(&req, posts).into()
+/// }
+/// ```
+pub struct Negotiated<T> {
+  value: T,
+  accept: Option<Accept>,
+  formats: Vec<(Mime, SerializeFn<T>)>,
+}
+
+impl<T: Serialize + 'static> Negotiated<T> {
+  /// Creates a response offering JSON, MessagePack, CBOR and CSV.
+  pub fn new(req: &Request, value: T) -> Self {
+    Self::without_formats(req, value)
+      .format(Json)
+      .format(MessagePack)
+      .format(Cbor)
+      .format(Csv)
+  }
+
+  /// Creates a response offering no formats, to be added with
+  /// [`format`](Negotiated::format).
+  pub fn without_formats(req: &Request, value: T) -> Self {
+    Self {
+      value,
+      // a missing or invalid header accepts anything
+      accept: req.typed_header(),
+      formats: Vec::new(),
+    }
+  }
+
+  /// Offers `format`, with lower preference than the formats already
+  /// offered. A format with the same media type as an existing one
+  /// replaces it.
+  pub fn format<F: Format>(mut self, format: F) -> Self {
+    let media_type = format.media_type();
+    let serialize: SerializeFn<T> = Box::new(move |value| format.serialize(value));
+
+    match self
+      .formats
+      .iter_mut()
+      .find(|(existing, _)| *existing == media_type)
+    {
+      Some(existing) => existing.1 = serialize,
+      None => self.formats.push((media_type, serialize)),
+    }
+    self
+  }
+}
+
+impl<T> From<Negotiated<T>> for Response {
+  fn from(negotiated: Negotiated<T>) -> Self {
+    let Negotiated {
+      value,
+      accept,
+      formats,
+    } = negotiated;
+
+    let selected = match &accept {
+      Some(accept) => accept
+        .preferred(formats.iter().map(|(media_type, _)| media_type))
+        .and_then(|selected| {
+          formats
+            .iter()
+            .find(|(media_type, _)| media_type == selected)
+        }),
+      None => formats.first(),
+    };
+
+    let mut res = match selected {
+      Some((media_type, serialize)) => match serialize(&value) {
+        Ok(body) => {
+          let mut res = Response::new(body);
+          let content_type = HeaderValue::from_str(media_type.as_ref()).unwrap();
+          res.headers_mut().insert(header::CONTENT_TYPE, content_type);
+          res
+        }
+        Err(err) => {
+          log::error!("failed to serialize response as {}: {}", media_type, err);
+          let mut res = Response::new(Body::empty());
+          *res.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+          res
+        }
+      },
+      None => {
+        let available: Vec<_> = formats
+          .iter()
+          .map(|(media_type, _)| media_type.as_ref())
+          .collect();
+        let mut res = Response::text(available.join("\n"));
+        *res.status_mut() = StatusCode::NOT_ACCEPTABLE;
+        res
+      }
+    };
+
+    res
+      .headers_mut()
+      .append(header::VARY, HeaderValue::from_static("accept"));
+    res
+  }
+}
+
+/// A serialization format for [`Negotiated`] responses.
+///
+/// ```rust
+/// use serde::Serialize;
+/// use turbofish::http::Format;
+///
+/// struct Yaml;
+///
+/// impl Format for Yaml {
+///   fn media_type(&self) -> mime::Mime {
+///     "application/yaml".parse().unwrap()
+///   }
+///
+///   fn serialize<T: Serialize + ?Sized>(
+///     &self,
+///     value: &T,
+///   ) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+///     # let to_yaml = |_: &T| -> Result<Vec<u8>, std::fmt::Error> { Ok(Vec::new()) };
+///     Ok(to_yaml(value)?)
+///   }
+/// }
+/// ```
+pub trait Format: Send + Sync + 'static {
+  /// Returns the media type of the format, matched against the `Accept`
+  /// header and sent as the `Content-Type`.
+  fn media_type(&self) -> Mime;
+
+  fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, BoxError>;
+}
+
+/// JSON, as `application/json`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Json;
+
+impl Format for Json {
+  fn media_type(&self) -> Mime {
+    mime::APPLICATION_JSON
+  }
+
+  fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, BoxError> {
+    Ok(serde_json::to_vec(value)?)
+  }
+}
+
+/// MessagePack, as `application/msgpack`. Structs are serialized as maps
+/// keyed by field name.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MessagePack;
+
+impl Format for MessagePack {
+  fn media_type(&self) -> Mime {
+    "application/msgpack".parse().unwrap()
+  }
+
+  fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, BoxError> {
+    Ok(rmp_serde::to_vec_named(value)?)
+  }
+}
+
+/// CBOR, as `application/cbor`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Cbor;
+
+impl Format for Cbor {
+  fn media_type(&self) -> Mime {
+    "application/cbor".parse().unwrap()
+  }
+
+  fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, BoxError> {
+    let mut out = Vec::new();
+    ciborium::ser::into_writer(value, &mut out)?;
+    Ok(out)
+  }
+}
+
+/// CSV, as `text/csv`.
+///
+/// The value must be a sequence, and each element is written as a record.
+/// When the elements are structs, a header row is written from their
+/// field names.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Csv;
+
+impl Format for Csv {
+  fn media_type(&self) -> Mime {
+    "text/csv; charset=utf-8".parse().unwrap()
+  }
+
+  fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, BoxError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    value.serialize(Records(&mut writer))?;
+    Ok(writer.into_inner().map_err(|err| err.into_error())?)
+  }
+}
+
+/// Writes each element of a sequence as a CSV record.
+struct Records<'a>(&'a mut csv::Writer<Vec<u8>>);
+
+impl<'a> ser::SerializeSeq for Records<'a> {
+  type Ok = ();
+  type Error = csv::Error;
+
+  fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), csv::Error> {
+    self.0.serialize(value)
+  }
+
+  fn end(self) -> Result<(), csv::Error> {
+    Ok(())
+  }
+}
+
+macro_rules! not_records {
+  ($($method:ident($($arg:ty),*) -> $out:ty;)*) => {$(
+    fn $method(self, $(_: $arg),*) -> Result<$out, csv::Error> {
+      Err(ser::Error::custom("CSV responses must be a sequence of records"))
+    }
+  )*};
+}
+
+impl<'a> ser::Serializer for Records<'a> {
+  type Ok = ();
+  type Error = csv::Error;
+  type SerializeSeq = Self;
+  type SerializeTuple = Impossible<(), csv::Error>;
+  type SerializeTupleStruct = Impossible<(), csv::Error>;
+  type SerializeTupleVariant = Impossible<(), csv::Error>;
+  type SerializeMap = Impossible<(), csv::Error>;
+  type SerializeStruct = Impossible<(), csv::Error>;
+  type SerializeStructVariant = Impossible<(), csv::Error>;
+
+  fn serialize_seq(self, _: Option<usize>) -> Result<Self, csv::Error> {
+    Ok(self)
+  }
+
+  fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), csv::Error> {
+    value.serialize(self)
+  }
+
+  fn serialize_newtype_struct<T: Serialize + ?Sized>(
+    self,
+    _: &'static str,
+    value: &T,
+  ) -> Result<(), csv::Error> {
+    value.serialize(self)
+  }
+
+  fn serialize_newtype_variant<T: Serialize + ?Sized>(
+    self,
+    _: &'static str,
+    _: u32,
+    _: &'static str,
+    _: &T,
+  ) -> Result<(), csv::Error> {
+    Err(ser::Error::custom(
+      "CSV responses must be a sequence of records",
+    ))
+  }
+
+  not_records! {
+    serialize_bool(bool) -> ();
+    serialize_i8(i8) -> ();
+    serialize_i16(i16) -> ();
+    serialize_i32(i32) -> ();
+    serialize_i64(i64) -> ();
+    serialize_u8(u8) -> ();
+    serialize_u16(u16) -> ();
+    serialize_u32(u32) -> ();
+    serialize_u64(u64) -> ();
+    serialize_f32(f32) -> ();
+    serialize_f64(f64) -> ();
+    serialize_char(char) -> ();
+    serialize_str(&str) -> ();
+    serialize_bytes(&[u8]) -> ();
+    serialize_none() -> ();
+    serialize_unit() -> ();
+    serialize_unit_struct(&'static str) -> ();
+    serialize_unit_variant(&'static str, u32, &'static str) -> ();
+    serialize_tuple(usize) -> Self::SerializeTuple;
+    serialize_tuple_struct(&'static str, usize) -> Self::SerializeTupleStruct;
+    serialize_tuple_variant(&'static str, u32, &'static str, usize) -> Self::SerializeTupleVariant;
+    serialize_map(Option<usize>) -> Self::SerializeMap;
+    serialize_struct(&'static str, usize) -> Self::SerializeStruct;
+    serialize_struct_variant(&'static str, u32, &'static str, usize) -> Self::SerializeStructVariant;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Serialize)]
+  struct Post {
+    id: u64,
+    title: &'static str,
+  }
+
+  fn negotiate(accept: Option<&str>) -> Response {
+    let mut req = hyper::Request::get("/posts");
+    if let Some(accept) = accept {
+      req = req.header(header::ACCEPT, accept);
+    }
+    let req: Request = req.body(hyper::Body::empty()).unwrap().into();
+
+    let posts = vec![
+      Post {
+        id: 1,
+        title: "Hello, world",
+      },
+      Post {
+        id: 2,
+        title: "Bye",
+      },
+    ];
+    Negotiated::new(&req, posts).into()
+  }
+
+  fn body(res: &Response) -> &[u8] {
+    match res.body() {
+      Body::Once(bytes) => bytes,
+      _ => panic!("expected a buffered body"),
+    }
+  }
+
+  #[test]
+  fn negotiated() {
+    let res = negotiate(None);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "application/json");
+    assert_eq!(res.headers()[header::VARY], "accept");
+
+    let res = negotiate(Some("application/cbor;q=0.5, application/msgpack"));
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "application/msgpack");
+    assert_eq!(body(&res)[0], 0x92);
+
+    let res = negotiate(Some("text/*"));
+    assert_eq!(
+      res.headers()[header::CONTENT_TYPE],
+      "text/csv; charset=utf-8"
+    );
+    assert_eq!(body(&res), b"id,title\n1,\"Hello, world\"\n2,Bye\n");
+
+    let res = negotiate(Some("image/png"));
+    assert_eq!(res.status(), StatusCode::NOT_ACCEPTABLE);
+    assert_eq!(res.headers()[header::VARY], "accept");
+
+    let req: Request = hyper::Request::get("/")
+      .header(header::ACCEPT, "text/csv")
+      .body(hyper::Body::empty())
+      .unwrap()
+      .into();
+    let res: Response = Negotiated::new(&req, Post { id: 1, title: "" }).into();
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+  }
+}
@@ -0,0 +1,119 @@
+use crate::http::form::{self, Fields, Value};
+use crate::http::StatusCode;
+use serde::de::{DeserializeOwned, IntoDeserializer};
+use std::error::Error;
+use std::fmt;
+
+/// An error returned when the query string doesn't match the expected
+/// type.
+#[derive(Debug)]
+pub struct QueryError(form::Error);
+
+impl QueryError {
+  /// Returns the status code the request should be answered with.
+  pub fn status(&self) -> StatusCode {
+    StatusCode::BAD_REQUEST
+  }
+}
+
+impl fmt::Display for QueryError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "invalid query string: {}", self.0)
+  }
+}
+
+impl Error for QueryError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    Some(&self.0)
+  }
+}
+
+pub(crate) fn parse<T: DeserializeOwned>(query: &str) -> Result<T, QueryError> {
+  let mut fields = Fields::default();
+  for (name, value) in form_urlencoded::parse(query.as_bytes()) {
+    fields
+      .insert(&name, Value::Text(value.into_owned()))
+      .map_err(QueryError)?;
+  }
+  T::deserialize(fields.into_deserializer()).map_err(QueryError)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde::Deserialize;
+  use std::collections::HashMap;
+
+  #[derive(Deserialize, Debug, PartialEq)]
+  #[serde(rename_all = "lowercase")]
+  enum Status {
+    Open,
+    Closed,
+  }
+
+  #[derive(Deserialize, Debug, PartialEq)]
+  struct Filter {
+    status: Status,
+    author: Option<String>,
+  }
+
+  #[derive(Deserialize, Debug, PartialEq)]
+  struct Query {
+    #[serde(default)]
+    tag: Vec<String>,
+    filter: Option<Filter>,
+    page: Option<u32>,
+    #[serde(default)]
+    sort: Vec<HashMap<String, String>>,
+  }
+
+  #[test]
+  fn query() {
+    let query: Query = parse("tag=a&tag=b&filter[status]=open&page=2").unwrap();
+    assert_eq!(query.tag, vec!["a", "b"]);
+    assert_eq!(
+      query.filter,
+      Some(Filter {
+        status: Status::Open,
+        author: None,
+      })
+    );
+    assert_eq!(query.page, Some(2));
+
+    let query: Query = parse("tag[]=a&filter%5Bstatus%5D=closed&filter[author]=ferris").unwrap();
+    assert_eq!(query.tag, vec!["a"]);
+    assert_eq!(query.filter.unwrap().author.as_deref(), Some("ferris"));
+
+    let query: Query = parse("sort[1][field]=name&sort[0][field]=date&sort[0][dir]=desc").unwrap();
+    assert_eq!(query.sort.len(), 2);
+    assert_eq!(query.sort[0]["dir"], "desc");
+    assert_eq!(query.sort[1]["field"], "name");
+
+    let query: Query = parse("").unwrap();
+    assert_eq!(query.tag, Vec::<String>::new());
+
+    let err = parse::<Query>("page=two").unwrap_err();
+    assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+    assert!(err.to_string().contains("two"), "{}", err);
+
+    assert!(parse::<Query>("page=1&page=2").is_err());
+    assert!(parse::<Query>("filter=open&filter[status]=open").is_err());
+    assert!(parse::<Query>("filter[status]=pending").is_err());
+  }
+
+  #[test]
+  fn limits() {
+    let nested = format!("a{}=1", "[a]".repeat(form::MAX_DEPTH - 1));
+    assert!(parse::<HashMap<String, serde_json::Value>>(&nested).is_ok());
+
+    let nested = format!("a{}=1", "[a]".repeat(100_000));
+    let err = parse::<HashMap<String, serde_json::Value>>(&nested).unwrap_err();
+    assert!(err.to_string().contains("nested too deeply"), "{}", err);
+
+    let fields = vec!["tag=a"; form::MAX_FIELDS].join("&");
+    assert_eq!(parse::<Query>(&fields).unwrap().tag.len(), form::MAX_FIELDS);
+
+    let err = parse::<Query>(&format!("{}&tag=a", fields)).unwrap_err();
+    assert!(err.to_string().contains("too many fields"), "{}", err);
+  }
+}
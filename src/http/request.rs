@@ -1,16 +1,358 @@
-use crate::http::{Method, Uri, HeaderValue, HeaderMap, CookieJar};
+use crate::http::{header, query, Body, BodyError, CookieJar, Multipart, QueryError, Extensions, HeaderMap, HeaderValue, Method, Uri, Version};
+use crate::router::Route;
+use bytes::Bytes;
+use headers::{Header, HeaderMapExt};
+use encoding_rs::{Encoding, UTF_8};
+use mime::Mime;
+use serde::de::DeserializeOwned;
+use std::net::SocketAddr;
+use std::sync::Arc;
 
 /// The HTTP request header consists of a method, uri, cookie jar, and a set of
 /// header fields.
 pub struct RequestHeader {
     method: Method,
     uri: Uri,
+    version: Version,
     headers: HeaderMap<HeaderValue>,
-    cookies: CookieJar
+    cookies: CookieJar,
 }
 
 impl RequestHeader {
     pub fn method(&self) -> &Method {
         &self.method
     }
+
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn headers(&self) -> &HeaderMap<HeaderValue> {
+        &self.headers
+    }
+
+    pub fn cookies(&self) -> &CookieJar {
+        &self.cookies
+    }
+
+    /// Returns the typed header `H`, or `None` if it is missing or invalid.
+    pub fn typed_header<H: Header>(&self) -> Option<H> {
+        self.headers.typed_get()
+    }
+
+    /// Returns the typed header `H`, or an error if it is invalid.
+    pub fn try_typed_header<H: Header>(&self) -> Result<Option<H>, headers::Error> {
+        self.headers.typed_try_get()
+    }
+}
+
+/// An HTTP request.
+pub struct Request {
+    header: RequestHeader,
+    body: Body,
+    extensions: Extensions,
+    remote_addr: Option<SocketAddr>,
+}
+
+impl Request {
+    pub fn header(&self) -> &RequestHeader {
+        &self.header
+    }
+
+    pub fn method(&self) -> &Method {
+        &self.header.method
+    }
+
+    pub fn method_mut(&mut self) -> &mut Method {
+        &mut self.header.method
+    }
+
+    pub fn uri(&self) -> &Uri {
+        &self.header.uri
+    }
+
+    pub fn uri_mut(&mut self) -> &mut Uri {
+        &mut self.header.uri
+    }
+
+    /// Returns the path component of the request uri.
+    pub fn path(&self) -> &str {
+        self.header.uri.path()
+    }
+
+    /// Returns the raw query string of the request uri, if any.
+    pub fn query_string(&self) -> Option<&str> {
+        self.header.uri.query()
+    }
+
+    /// Deserializes the query string.
+    ///
+    /// Repeated keys (`?tag=a&tag=b`) and empty brackets (`?tag[]=a`)
+    /// deserialize into sequences, and bracket notation
+    /// (`?filter[status]=open`) into nested maps or structs. Indices
+    /// (`?sort[0][field]=date`) deserialize into sequences in index order.
+    ///
+    /// ```rust
+    /// use serde::Deserialize;
+    /// use turbofish::http::{Request, Response};
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Filter {
+    ///     status: Option<String>,
+    /// }
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Search {
+    ///     #[serde(default)]
+    ///     tag: Vec<String>,
+    ///     filter: Option<Filter>,
+    ///     page: Option<u32>,
+    /// }
+    ///
+    /// async fn search(req: Request) -> Response {
+    ///     match req.query::<Search>() {
+    ///         Ok(search) => Response::text(format!("{} tags", search.tag.len())),
+    ///         Err(err) => Response::text(err.to_string()),
+    ///     }
+    /// }
+    /// ```
+    pub fn query<T: DeserializeOwned>(&self) -> Result<T, QueryError> {
+        query::parse(self.query_string().unwrap_or_default())
+    }
+
+    pub fn version(&self) -> Version {
+        self.header.version
+    }
+
+    pub fn headers(&self) -> &HeaderMap<HeaderValue> {
+        &self.header.headers
+    }
+
+    pub fn headers_mut(&mut self) -> &mut HeaderMap<HeaderValue> {
+        &mut self.header.headers
+    }
+
+    pub fn cookies(&self) -> &CookieJar {
+        &self.header.cookies
+    }
+
+    /// Returns the typed header `H`, or `None` if it is missing or invalid.
+    ///
+    /// See [`headers`](crate::http::headers) for the available headers.
+    pub fn typed_header<H: Header>(&self) -> Option<H> {
+        self.header.typed_header()
+    }
+
+    /// Returns the typed header `H`, or an error if it is invalid.
+    pub fn try_typed_header<H: Header>(&self) -> Result<Option<H>, headers::Error> {
+        self.header.try_typed_header()
+    }
+
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    pub fn body(&self) -> &Body {
+        &self.body
+    }
+
+    pub fn body_mut(&mut self) -> &mut Body {
+        &mut self.body
+    }
+
+    /// Takes the body out of the request, leaving an empty body in its place.
+    pub fn take_body(&mut self) -> Body {
+        std::mem::take(&mut self.body)
+    }
+
+    /// Reads the whole body into memory. The body is taken out of the
+    /// request, as with [`take_body`](Request::take_body).
+    pub async fn bytes(&mut self) -> Result<Bytes, BodyError> {
+        self.take_body().bytes().await
+    }
+
+    /// Reads the body as text, decoded with the charset of the
+    /// `Content-Type` header (default is UTF-8).
+    pub async fn text(&mut self) -> Result<String, BodyError> {
+        let encoding = match self.content_type().as_ref().and_then(|mime| mime.get_param(mime::CHARSET)) {
+            Some(charset) => {
+                Encoding::for_label(charset.as_str().as_bytes()).ok_or(BodyError::UnsupportedMediaType)?
+            }
+            None => UTF_8,
+        };
+
+        let bytes = self.bytes().await?;
+        encoding
+            .decode_without_bom_handling_and_without_replacement(&bytes)
+            .map(|text| text.into_owned())
+            .ok_or(BodyError::InvalidText)
+    }
+
+    /// Deserializes a JSON body. Requests without an `application/json`
+    /// (or `+json`) content type are rejected.
+    pub async fn json<T: DeserializeOwned>(&mut self) -> Result<T, BodyError> {
+        let is_json = self.content_type().is_some_and(|mime| {
+            mime.essence_str() == mime::APPLICATION_JSON.essence_str() || mime.suffix() == Some(mime::JSON)
+        });
+
+        if !is_json {
+            return Err(BodyError::UnsupportedMediaType);
+        }
+
+        serde_json::from_slice(&self.bytes().await?).map_err(BodyError::Json)
+    }
+
+    /// Deserializes an `application/x-www-form-urlencoded` body.
+    pub async fn form<T: DeserializeOwned>(&mut self) -> Result<T, BodyError> {
+        let is_form = self.content_type().is_some_and(|mime| {
+            mime.essence_str() == mime::APPLICATION_WWW_FORM_URLENCODED.essence_str()
+        });
+
+        if !is_form {
+            return Err(BodyError::UnsupportedMediaType);
+        }
+
+        serde_urlencoded::from_bytes(&self.bytes().await?).map_err(BodyError::Form)
+    }
+
+    /// Returns a streaming parser for a `multipart/form-data` body. The
+    /// body is taken out of the request, as with
+    /// [`take_body`](Request::take_body).
+    pub fn multipart(&mut self) -> Result<Multipart, BodyError> {
+        let boundary = self
+            .content_type()
+            .filter(|mime| mime.essence_str() == mime::MULTIPART_FORM_DATA.essence_str())
+            .and_then(|mime| Some(mime.get_param(mime::BOUNDARY)?.to_string()))
+            .ok_or(BodyError::UnsupportedMediaType)?;
+
+        Ok(Multipart::new(self.take_body(), boundary))
+    }
+
+    pub(crate) fn content_type(&self) -> Option<Mime> {
+        self.header.headers.get(header::CONTENT_TYPE)?.to_str().ok()?.parse().ok()
+    }
+
+    /// Returns the address of the client that sent the request, if known.
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.remote_addr
+    }
+
+    pub(crate) fn set_remote_addr(&mut self, addr: SocketAddr) {
+        self.remote_addr = Some(addr);
+    }
+
+    /// Returns the `Last-Event-ID` header sent by Server-Sent Events clients
+    /// when reconnecting.
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.header.headers.get("last-event-id")?.to_str().ok()
+    }
+
+    /// Returns the route that matched this request, if any.
+    pub fn route(&self) -> Option<&Route> {
+        self.extensions.get::<Arc<Route>>().map(|route| &**route)
+    }
+}
+
+impl From<hyper::Request<hyper::Body>> for Request {
+    fn from(req: hyper::Request<hyper::Body>) -> Self {
+        let (parts, body) = req.into_parts();
+        let cookies = CookieJar::from_headers(&parts.headers);
+        Self {
+            header: RequestHeader {
+                method: parts.method,
+                uri: parts.uri,
+                version: parts.version,
+                headers: parts.headers,
+                cookies,
+            },
+            body: body.into(),
+            extensions: parts.extensions,
+            remote_addr: None,
+        }
+    }
+}
+
+impl From<Request> for hyper::Request<hyper::Body> {
+    fn from(req: Request) -> Self {
+        let mut builder = hyper::Request::builder()
+            .method(req.header.method)
+            .uri(req.header.uri)
+            .version(req.header.version);
+        *builder.headers_mut().unwrap() = req.header.headers;
+        *builder.extensions_mut().unwrap() = req.extensions;
+        builder.body(req.body.into()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::header;
+    use bytes::Bytes;
+
+    #[derive(Debug, PartialEq)]
+    struct Marker(u32);
+
+    #[tokio::test]
+    async fn from_hyper() {
+        let mut req = hyper::Request::builder()
+            .method(Method::PUT)
+            .uri("/users/1?tab=posts")
+            .version(Version::HTTP_2)
+            .header(header::CONTENT_TYPE, "text/plain")
+            .header(header::COOKIE, "theme=dark")
+            .body(hyper::Body::from("hello"))
+            .unwrap();
+        req.extensions_mut().insert(Marker(1));
+
+        let mut req = Request::from(req);
+        assert_eq!(*req.method(), Method::PUT);
+        assert_eq!(req.path(), "/users/1");
+        assert_eq!(req.query_string(), Some("tab=posts"));
+        assert_eq!(req.version(), Version::HTTP_2);
+        assert_eq!(req.headers()[header::CONTENT_TYPE], "text/plain");
+        assert_eq!(req.cookies().get("theme").unwrap().value(), "dark");
+        assert_eq!(req.extensions().get::<Marker>(), Some(&Marker(1)));
+        assert_eq!(req.remote_addr(), None);
+
+        let body = hyper::body::to_bytes(hyper::Body::from(req.take_body())).await.unwrap();
+        assert_eq!(body, "hello");
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let mut req = hyper::Request::builder()
+            .method(Method::POST)
+            .uri("/search?q=turbofish")
+            .header(header::ACCEPT, "application/json")
+            .body(hyper::Body::from("query"))
+            .unwrap();
+        req.extensions_mut().insert(Marker(2));
+
+        let mut req = Request::from(req);
+        *req.method_mut() = Method::PATCH;
+        *req.uri_mut() = "/search?q=turbofish&page=2".parse().unwrap();
+        req.headers_mut().insert(header::ACCEPT_LANGUAGE, HeaderValue::from_static("en"));
+
+        let req = hyper::Request::from(req);
+        assert_eq!(req.method(), Method::PATCH);
+        assert_eq!(req.uri(), "/search?q=turbofish&page=2");
+        assert_eq!(req.headers()[header::ACCEPT], "application/json");
+        assert_eq!(req.headers()[header::ACCEPT_LANGUAGE], "en");
+        assert_eq!(req.extensions().get::<Marker>(), Some(&Marker(2)));
+        assert_eq!(hyper::body::to_bytes(req.into_body()).await.unwrap(), "query");
+
+        let mut req: Request = hyper::Request::get("/").body(hyper::Body::empty()).unwrap().into();
+        *req.body_mut() = Body::Once(Bytes::from_static(b"replaced"));
+        let req = hyper::Request::from(req);
+        assert_eq!(hyper::body::to_bytes(req.into_body()).await.unwrap(), "replaced");
+    }
 }
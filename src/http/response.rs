@@ -1,7 +1,236 @@
-use crate::http::{HeaderMap, HeaderValue, StatusCode, Body};
+use crate::http::{header, Body, Cookie, HeaderMap, HeaderName, HeaderValue, StatusCode};
+use headers::{Header, HeaderMapExt};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::Serialize;
+use std::convert::TryFrom;
 
+/// Characters that can't appear in a `Location` header as is.
+const LOCATION: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'<').add(b'>').add(b'`');
+
+/// An HTTP response.
 pub struct Response {
   status: StatusCode,
   headers: HeaderMap<HeaderValue>,
   body: Body,
 }
+
+impl Response {
+  /// Creates a `200 OK` response with the given body.
+  pub fn new(body: impl Into<Body>) -> Self {
+    Self {
+      status: StatusCode::OK,
+      headers: HeaderMap::new(),
+      body: body.into(),
+    }
+  }
+
+  pub fn builder() -> Builder {
+    Builder {
+      inner: http::response::Builder::new(),
+    }
+  }
+
+  /// Creates a response with `value` serialized as JSON. If serialization
+  /// fails, the error is logged and a `500 Internal Server Error` is
+  /// returned instead.
+  pub fn json<T: Serialize + ?Sized>(value: &T) -> Self {
+    match serde_json::to_vec(value) {
+      Ok(json) => Self::with_type(json, "application/json"),
+      Err(err) => {
+        log::error!("failed to serialize response: {}", err);
+        Self::empty(StatusCode::INTERNAL_SERVER_ERROR)
+      }
+    }
+  }
+
+  /// Creates a response with a `text/html` body.
+  pub fn html(body: impl Into<Body>) -> Self {
+    Self::with_type(body, "text/html; charset=utf-8")
+  }
+
+  /// Creates a response with a `text/plain` body.
+  pub fn text(body: impl Into<Body>) -> Self {
+    Self::with_type(body, "text/plain; charset=utf-8")
+  }
+
+  /// Creates a redirect to `to`, which is percent-encoded where needed to
+  /// form a valid `Location` header.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `status` is not a `3xx` status code.
+  pub fn redirect(to: &str, status: StatusCode) -> Self {
+    assert!(status.is_redirection(), "{} is not a redirect status", status);
+
+    let location = utf8_percent_encode(to, LOCATION).to_string();
+    let mut res = Self::empty(status);
+    res.headers.insert(header::LOCATION, HeaderValue::from_str(&location).unwrap());
+    res
+  }
+
+  /// Creates an empty `204 No Content` response.
+  pub fn no_content() -> Self {
+    Self::empty(StatusCode::NO_CONTENT)
+  }
+
+  fn empty(status: StatusCode) -> Self {
+    let mut res = Self::new(Body::empty());
+    res.status = status;
+    res
+  }
+
+  fn with_type(body: impl Into<Body>, content_type: &'static str) -> Self {
+    let mut res = Self::new(body);
+    res.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    res
+  }
+
+  pub fn status(&self) -> StatusCode {
+    self.status
+  }
+
+  pub fn status_mut(&mut self) -> &mut StatusCode {
+    &mut self.status
+  }
+
+  pub fn headers(&self) -> &HeaderMap<HeaderValue> {
+    &self.headers
+  }
+
+  pub fn headers_mut(&mut self) -> &mut HeaderMap<HeaderValue> {
+    &mut self.headers
+  }
+
+  /// Returns the typed header `H`, or `None` if it is missing or invalid.
+  pub fn typed_header<H: Header>(&self) -> Option<H> {
+    self.headers.typed_get()
+  }
+
+  /// Sets a typed header, replacing any values of the same header.
+  pub fn set_typed_header<H: Header>(&mut self, header: H) {
+    self.headers.typed_insert(header);
+  }
+
+  pub fn body(&self) -> &Body {
+    &self.body
+  }
+
+  pub fn body_mut(&mut self) -> &mut Body {
+    &mut self.body
+  }
+
+  pub fn into_body(self) -> Body {
+    self.body
+  }
+}
+
+/// A builder for responses, created with [`Response::builder`].
+///
+/// Errors from invalid statuses or headers are deferred until
+/// [`body`](Builder::body) is called.
+pub struct Builder {
+  inner: http::response::Builder,
+}
+
+impl Builder {
+  pub fn status<T>(self, status: T) -> Self
+  where
+    StatusCode: TryFrom<T>,
+    <StatusCode as TryFrom<T>>::Error: Into<http::Error>,
+  {
+    Self {
+      inner: self.inner.status(status),
+    }
+  }
+
+  /// Appends a raw header.
+  pub fn header<K, V>(self, key: K, value: V) -> Self
+  where
+    HeaderName: TryFrom<K>,
+    <HeaderName as TryFrom<K>>::Error: Into<http::Error>,
+    HeaderValue: TryFrom<V>,
+    <HeaderValue as TryFrom<V>>::Error: Into<http::Error>,
+  {
+    Self {
+      inner: self.inner.header(key, value),
+    }
+  }
+
+  /// Sets a typed header, replacing any values of the same header.
+  pub fn typed_header<H: Header>(mut self, header: H) -> Self {
+    if let Some(headers) = self.inner.headers_mut() {
+      headers.typed_insert(header);
+    }
+    self
+  }
+
+  /// Appends a `Set-Cookie` header for `cookie`.
+  pub fn cookie(self, cookie: Cookie<'_>) -> Self {
+    self.header(header::SET_COOKIE, cookie.to_string())
+  }
+
+  /// Consumes the builder, returning the response with the given body.
+  pub fn body(self, body: impl Into<Body>) -> http::Result<Response> {
+    let (parts, ()) = self.inner.body(())?.into_parts();
+    Ok(Response {
+      status: parts.status,
+      headers: parts.headers,
+      body: body.into(),
+    })
+  }
+}
+
+impl From<hyper::Response<hyper::Body>> for Response {
+  fn from(res: hyper::Response<hyper::Body>) -> Self {
+    let (parts, body) = res.into_parts();
+    Self {
+      status: parts.status,
+      headers: parts.headers,
+      body: body.into(),
+    }
+  }
+}
+
+impl From<Response> for hyper::Response<Body> {
+  fn from(res: Response) -> Self {
+    let mut out = hyper::Response::new(res.body);
+    *out.status_mut() = res.status;
+    *out.headers_mut() = res.headers;
+    out
+  }
+}
+
+impl From<Response> for hyper::Response<hyper::Body> {
+  fn from(res: Response) -> Self {
+    let mut out = hyper::Response::new(res.body.into());
+    *out.status_mut() = res.status;
+    *out.headers_mut() = res.headers;
+    out
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn constructors() {
+    let res = Response::json(&serde_json::json!({ "ok": true }));
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "application/json");
+    assert!(matches!(res.body(), Body::Once(json) if json == "{\"ok\":true}"));
+
+    let res = Response::redirect("/search?q=a b", StatusCode::SEE_OTHER);
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+    assert_eq!(res.headers()[header::LOCATION], "/search?q=a%20b");
+
+    let res = Response::builder()
+      .typed_header(headers::ContentLength(5))
+      .cookie(Cookie::new("theme", "dark"))
+      .body("hello")
+      .unwrap();
+    assert_eq!(res.headers()[header::CONTENT_LENGTH], "5");
+    assert_eq!(res.headers()[header::SET_COOKIE], "theme=dark");
+
+    assert!(Response::builder().header("bad header", "").body(Body::empty()).is_err());
+  }
+}
@@ -0,0 +1,296 @@
+use crate::http::{header, Body, HeaderValue, Response};
+use bytes::Bytes;
+use futures::Stream;
+use serde::Serialize;
+use std::error::Error;
+use std::fmt::Write;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::{Instant, Sleep};
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// A Server-Sent Events response, streaming events to the client as they
+/// are produced.
+///
+/// A comment is sent whenever no event has been sent for the keep-alive
+/// interval, so that proxies don't close idle connections. Clients
+/// reconnecting after a dropped connection send the id of the last event
+/// they received, available with
+/// [`Request::last_event_id`](crate::http::Request::last_event_id).
+///
+/// ```rust
+/// use futures::stream;
+/// use std::convert::Infallible;
+/// use turbofish::http::{Event, Request, Response, Sse};
+///
+/// async fn updates(req: Request) -> Response {
+///   let start: u64 = req.last_event_id().and_then(|id| id.parse().ok()).unwrap_or(0);
+///   let events = stream::iter((start + 1..).map(|n| {
+///     Ok::<_, Infallible>(Event::new().id(n.to_string()).event("tick").data(n.to_string()))
+///   }));
+///
+///   Sse::new(events).into()
+/// }
+/// ```
+pub struct Sse<S> {
+  events: S,
+  keep_alive: Option<Duration>,
+}
+
+impl<S, E> Sse<S>
+where
+  S: Stream<Item = Result<Event, E>> + Send + 'static,
+  E: Into<BoxError>,
+{
+  pub fn new(events: S) -> Self {
+    Self {
+      events,
+      keep_alive: Some(Duration::from_secs(15)),
+    }
+  }
+
+  /// Sets the keep-alive interval (default is 15 seconds).
+  pub fn keep_alive(mut self, interval: impl Into<Option<Duration>>) -> Self {
+    self.keep_alive = interval.into();
+    self
+  }
+}
+
+impl<S, E> From<Sse<S>> for Response
+where
+  S: Stream<Item = Result<Event, E>> + Send + 'static,
+  E: Into<BoxError>,
+{
+  fn from(sse: Sse<S>) -> Self {
+    let stream = KeepAlive {
+      events: Box::pin(sse.events),
+      keep_alive: sse.keep_alive,
+      sleep: sse
+        .keep_alive
+        .map(|interval| Box::pin(tokio::time::sleep(interval))),
+    };
+
+    let mut res = Response::new(Body::stream(stream));
+    let headers = res.headers_mut();
+    headers.insert(
+      header::CONTENT_TYPE,
+      HeaderValue::from_static("text/event-stream"),
+    );
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    // disable response buffering in nginx
+    headers.insert("x-accel-buffering", HeaderValue::from_static("no"));
+    res
+  }
+}
+
+/// A Server-Sent Event.
+#[derive(Clone, Debug, Default)]
+pub struct Event {
+  id: Option<String>,
+  event: Option<String>,
+  data: Option<String>,
+  retry: Option<Duration>,
+  comment: Option<String>,
+}
+
+impl Event {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Sets the id of the event, sent back by the client on reconnect.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `id` contains a newline or a null character.
+  pub fn id(mut self, id: impl Into<String>) -> Self {
+    let id = id.into();
+    assert!(
+      !id.contains(&['\n', '\r', '\0'][..]),
+      "SSE id cannot contain newlines or nulls"
+    );
+    self.id = Some(id);
+    self
+  }
+
+  /// Sets the type of the event.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `event` contains a newline.
+  pub fn event(mut self, event: impl Into<String>) -> Self {
+    let event = event.into();
+    assert!(
+      !event.contains(&['\n', '\r'][..]),
+      "SSE event cannot contain newlines"
+    );
+    self.event = Some(event);
+    self
+  }
+
+  /// Sets the data of the event. Multi-line data is sent as multiple
+  /// `data` fields.
+  pub fn data(mut self, data: impl Into<String>) -> Self {
+    self.data = Some(data.into());
+    self
+  }
+
+  /// Sets the data of the event to `value` serialized as JSON.
+  pub fn json<T: Serialize + ?Sized>(self, value: &T) -> serde_json::Result<Self> {
+    Ok(self.data(serde_json::to_string(value)?))
+  }
+
+  /// Sets how long the client should wait before reconnecting.
+  pub fn retry(mut self, retry: Duration) -> Self {
+    self.retry = Some(retry);
+    self
+  }
+
+  /// Sets a comment, ignored by clients.
+  pub fn comment(mut self, comment: impl Into<String>) -> Self {
+    self.comment = Some(comment.into());
+    self
+  }
+
+  fn encode(&self) -> Bytes {
+    let mut out = String::new();
+
+    if let Some(comment) = &self.comment {
+      for line in lines(comment) {
+        let _ = writeln!(out, ":{}", line);
+      }
+    }
+    if let Some(event) = &self.event {
+      let _ = writeln!(out, "event: {}", event);
+    }
+    if let Some(data) = &self.data {
+      for line in lines(data) {
+        let _ = writeln!(out, "data: {}", line);
+      }
+    }
+    if let Some(id) = &self.id {
+      let _ = writeln!(out, "id: {}", id);
+    }
+    if let Some(retry) = self.retry {
+      let _ = writeln!(out, "retry: {}", retry.as_millis());
+    }
+
+    out.push('\n');
+    out.into()
+  }
+}
+
+/// Splits `s` on the line endings recognized by clients, `\r\n`, `\r` and
+/// `\n`. Unlike `str::lines`, a trailing empty line is kept.
+fn lines(s: &str) -> Vec<&str> {
+  let bytes = s.as_bytes();
+  let mut lines = Vec::new();
+  let mut start = 0;
+  let mut i = 0;
+
+  while i < bytes.len() {
+    match bytes[i] {
+      b'\n' => {
+        lines.push(&s[start..i]);
+        start = i + 1;
+      }
+      b'\r' => {
+        lines.push(&s[start..i]);
+        if bytes.get(i + 1) == Some(&b'\n') {
+          i += 1;
+        }
+        start = i + 1;
+      }
+      _ => {}
+    }
+    i += 1;
+  }
+
+  lines.push(&s[start..]);
+  lines
+}
+
+/// Encodes events, sending a comment when the stream has been idle for the
+/// keep-alive interval.
+struct KeepAlive<S> {
+  events: Pin<Box<S>>,
+  keep_alive: Option<Duration>,
+  sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S, E> Stream for KeepAlive<S>
+where
+  S: Stream<Item = Result<Event, E>>,
+  E: Into<BoxError>,
+{
+  type Item = Result<Bytes, BoxError>;
+
+  fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    let this = &mut *self;
+
+    match this.events.as_mut().poll_next(cx) {
+      Poll::Ready(Some(event)) => {
+        if let (Some(sleep), Some(interval)) = (&mut this.sleep, this.keep_alive) {
+          sleep.as_mut().reset(Instant::now() + interval);
+        }
+        Poll::Ready(Some(event.map(|event| event.encode()).map_err(Into::into)))
+      }
+      Poll::Ready(None) => Poll::Ready(None),
+      Poll::Pending => {
+        if let (Some(sleep), Some(interval)) = (&mut this.sleep, this.keep_alive) {
+          if sleep.as_mut().poll(cx).is_ready() {
+            sleep.as_mut().reset(Instant::now() + interval);
+            return Poll::Ready(Some(Ok(Bytes::from_static(b":\n\n"))));
+          }
+        }
+        Poll::Pending
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use futures::StreamExt;
+  use std::convert::Infallible;
+
+  #[test]
+  fn encode() {
+    let event = Event::new()
+      .id("42")
+      .event("update")
+      .data("line one\nline two")
+      .retry(Duration::from_secs(3));
+    assert_eq!(
+      event.encode(),
+      "event: update\ndata: line one\ndata: line two\nid: 42\nretry: 3000\n\n"
+    );
+
+    assert_eq!(Event::new().comment("hi").encode(), ":hi\n\n");
+
+    // a lone `\r` ends a line too, and must not reach the client inside a field
+    let event = Event::new().comment("one\rtwo").data("a\r\nb\rc\n");
+    assert_eq!(
+      event.encode(),
+      ":one\n:two\ndata: a\ndata: b\ndata: c\ndata: \n\n"
+    );
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn keep_alive() {
+    let events = futures::stream::iter(vec![Ok::<_, Infallible>(Event::new().data("hello"))])
+      .chain(futures::stream::pending());
+
+    let res: Response = Sse::new(events).keep_alive(Duration::from_secs(1)).into();
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "text/event-stream");
+
+    let mut body = res.into_body();
+    assert_eq!(body.chunk().await.unwrap().unwrap(), "data: hello\n\n");
+    assert_eq!(body.chunk().await.unwrap().unwrap(), ":\n\n");
+    assert_eq!(body.chunk().await.unwrap().unwrap(), ":\n\n");
+  }
+}
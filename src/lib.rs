@@ -1,11 +1,14 @@
 pub mod action;
 pub mod resource;
 pub mod config;
+pub mod deadline;
+pub mod files;
 pub mod middleware;
 pub mod router;
 pub mod http;
 pub mod server;
 pub mod turbofish;
+pub mod websocket;
 
 pub use action::Action;
 pub use middleware::Middleware;
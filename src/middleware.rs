@@ -1 +0,0 @@
-pub trait Middleware {}
@@ -0,0 +1,381 @@
+pub mod jwt;
+
+use crate::http::{header, Body, HeaderValue, Request, Response, StatusCode};
+use crate::middleware::{Middleware, Next};
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+
+/// Credentials sent in the `Authorization` header.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Credentials {
+  /// `Basic` credentials, [RFC 7617](https://tools.ietf.org/html/rfc7617).
+  Basic { username: String, password: String },
+  /// A `Bearer` token, [RFC 6750](https://tools.ietf.org/html/rfc6750).
+  Bearer(String),
+}
+
+impl Credentials {
+  /// Parses the value of an `Authorization` header.
+  pub fn parse(value: &str) -> Result<Self, AuthError> {
+    let (scheme, params) = value.trim().split_once(' ').ok_or(AuthError::Malformed)?;
+    let params = params.trim();
+
+    if scheme.eq_ignore_ascii_case("basic") {
+      let decoded = base64::decode(params).map_err(|_| AuthError::Malformed)?;
+      let decoded = String::from_utf8(decoded).map_err(|_| AuthError::Malformed)?;
+      let (username, password) = decoded.split_once(':').ok_or(AuthError::Malformed)?;
+
+      Ok(Credentials::Basic {
+        username: username.to_owned(),
+        password: password.to_owned(),
+      })
+    } else if scheme.eq_ignore_ascii_case("bearer") {
+      if params.is_empty() || params.contains(' ') {
+        return Err(AuthError::Malformed);
+      }
+
+      Ok(Credentials::Bearer(params.to_owned()))
+    } else {
+      Err(AuthError::UnsupportedScheme)
+    }
+  }
+
+  pub fn scheme(&self) -> Scheme {
+    match self {
+      Credentials::Basic { .. } => Scheme::Basic,
+      Credentials::Bearer(_) => Scheme::Bearer,
+    }
+  }
+}
+
+// keep secrets out of logs
+impl fmt::Debug for Credentials {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Credentials::Basic { username, .. } => f
+        .debug_struct("Basic")
+        .field("username", username)
+        .finish_non_exhaustive(),
+      Credentials::Bearer(_) => f.write_str("Bearer(..)"),
+    }
+  }
+}
+
+/// An authentication scheme.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scheme {
+  Basic,
+  Bearer,
+}
+
+/// The reason a request failed authentication.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AuthError {
+  /// The route requires authentication but no credentials were sent.
+  Missing,
+  /// The `Authorization` header could not be parsed.
+  Malformed,
+  /// The credentials use a scheme that is not accepted.
+  UnsupportedScheme,
+  /// The credentials were rejected by the verifier, with a description
+  /// of why.
+  Invalid(String),
+}
+
+impl AuthError {
+  /// Returns the status code the request is answered with.
+  pub fn status(&self) -> StatusCode {
+    match self {
+      AuthError::Malformed => StatusCode::BAD_REQUEST,
+      _ => StatusCode::UNAUTHORIZED,
+    }
+  }
+}
+
+impl fmt::Display for AuthError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      AuthError::Missing => f.write_str("missing credentials"),
+      AuthError::Malformed => f.write_str("malformed authorization header"),
+      AuthError::UnsupportedScheme => f.write_str("unsupported authentication scheme"),
+      AuthError::Invalid(reason) => write!(f, "invalid credentials: {}", reason),
+    }
+  }
+}
+
+impl Error for AuthError {}
+
+/// Verifies credentials, resolving them to the authenticated principal,
+/// ex: a user.
+///
+/// Async closures taking [`Credentials`] and returning an
+/// `Option<Principal>` implement this trait.
+#[crate::async_trait]
+pub trait Verifier: Send + Sync {
+  type Principal: Send + Sync + 'static;
+
+  async fn verify(&self, credentials: Credentials) -> Result<Self::Principal, AuthError>;
+}
+
+#[crate::async_trait]
+impl<F, Fut, P> Verifier for F
+where
+  F: Fn(Credentials) -> Fut + Send + Sync,
+  Fut: Future<Output = Option<P>> + Send,
+  P: Send + Sync + 'static,
+{
+  type Principal = P;
+
+  async fn verify(&self, credentials: Credentials) -> Result<P, AuthError> {
+    self(credentials)
+      .await
+      .ok_or_else(|| AuthError::Invalid("invalid credentials".to_owned()))
+  }
+}
+
+/// Whether a route requires authentication, set with
+/// [`Route::auth`](crate::router::Route::auth).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuthRequirement {
+  /// Requests without valid credentials are rejected.
+  Required,
+  /// Requests without credentials are allowed through, but credentials
+  /// that are sent must be valid.
+  Optional,
+}
+
+/// Authentication middleware.
+///
+/// Credentials from the `Authorization` header are checked by the
+/// [`Verifier`], and the principal it returns is inserted into the request
+/// extensions. Requests with invalid credentials, or without credentials
+/// on routes that require them, are answered with `401 Unauthorized` and a
+/// `WWW-Authenticate` challenge for each accepted scheme.
+///
+/// ```rust
+/// use turbofish::http::{Method, Request, Response};
+/// use turbofish::middleware::auth::{AuthRequirement, Authenticate, Credentials};
+/// use turbofish::router::{Route, Router};
+///
+/// struct User(String);
+///
+/// let mut router = Router::default();
+/// router.route(
+///   Route::new(Method::GET, "/me", |req: Request| async move {
+///     let user = req.extensions().get::<User>().unwrap();
+///     Response::new(user.0.clone())
+///   })
+///   .auth(AuthRequirement::Required),
+/// );
+/// router.middleware(Authenticate::new(|credentials| async move {
+///   match credentials {
+///     Credentials::Bearer(token) if token == "secret" => Some(User("ferris".into())),
+///     _ => None,
+///   }
+/// }));
+/// ```
+pub struct Authenticate<V> {
+  verifier: V,
+  schemes: Vec<Scheme>,
+  realm: String,
+  requirement: AuthRequirement,
+  rejection: Box<dyn Fn(&AuthError) -> Response + Send + Sync>,
+}
+
+impl<V: Verifier> Authenticate<V> {
+  pub fn new(verifier: V) -> Self {
+    Self {
+      verifier,
+      schemes: vec![Scheme::Basic, Scheme::Bearer],
+      realm: "turbofish".to_owned(),
+      requirement: AuthRequirement::Optional,
+      rejection: Box::new(|err| {
+        Response::builder()
+          .status(err.status())
+          .body(Body::empty())
+          .unwrap()
+      }),
+    }
+  }
+
+  /// Sets the accepted schemes (default is both `Basic` and `Bearer`).
+  pub fn schemes(mut self, schemes: impl IntoIterator<Item = Scheme>) -> Self {
+    self.schemes = schemes.into_iter().collect();
+    self
+  }
+
+  /// Sets the realm sent in challenges (default is `turbofish`).
+  pub fn realm(mut self, realm: impl Into<String>) -> Self {
+    self.realm = realm.into();
+    self
+  }
+
+  /// Sets the requirement of routes that don't set their own (default is
+  /// [`AuthRequirement::Optional`]).
+  pub fn requirement(mut self, requirement: AuthRequirement) -> Self {
+    self.requirement = requirement;
+    self
+  }
+
+  /// Sets the response sent when authentication fails (default is an empty
+  /// `401 Unauthorized`, or `400 Bad Request` for malformed headers). The
+  /// `WWW-Authenticate` challenges are added to it.
+  pub fn rejection(mut self, f: impl Fn(&AuthError) -> Response + Send + Sync + 'static) -> Self {
+    self.rejection = Box::new(f);
+    self
+  }
+
+  fn reject(&self, err: AuthError) -> Response {
+    let mut res = (self.rejection)(&err);
+
+    if !res.headers().contains_key(header::WWW_AUTHENTICATE) {
+      for scheme in &self.schemes {
+        let challenge = match (scheme, &err) {
+          (Scheme::Basic, _) => format!(r#"Basic realm="{}", charset="UTF-8""#, self.realm),
+          (Scheme::Bearer, AuthError::Malformed) => {
+            format!(r#"Bearer realm="{}", error="invalid_request""#, self.realm)
+          }
+          (Scheme::Bearer, AuthError::Invalid(reason)) => format!(
+            r#"Bearer realm="{}", error="invalid_token", error_description="{}""#,
+            self.realm,
+            reason.replace('"', "'")
+          ),
+          (Scheme::Bearer, _) => format!(r#"Bearer realm="{}""#, self.realm),
+        };
+
+        if let Ok(value) = HeaderValue::from_str(&challenge) {
+          res.headers_mut().append(header::WWW_AUTHENTICATE, value);
+        }
+      }
+    }
+
+    res
+  }
+}
+
+#[crate::async_trait]
+impl<V: Verifier + 'static> Middleware for Authenticate<V> {
+  async fn call(&self, mut req: Request, next: Next<'_>) -> Response {
+    // unmatched requests are left to the router
+    let requirement = match req.route() {
+      Some(route) => route
+        .extensions()
+        .get::<AuthRequirement>()
+        .copied()
+        .unwrap_or(self.requirement),
+      None => return next.run(req).await,
+    };
+
+    let credentials = req.headers().get(header::AUTHORIZATION).map(|value| {
+      value
+        .to_str()
+        .map_err(|_| AuthError::Malformed)
+        .and_then(Credentials::parse)
+    });
+
+    let credentials = match credentials {
+      Some(Ok(credentials)) if self.schemes.contains(&credentials.scheme()) => credentials,
+      Some(Ok(_)) => return self.reject(AuthError::UnsupportedScheme),
+      Some(Err(err)) => return self.reject(err),
+      None if requirement == AuthRequirement::Required => return self.reject(AuthError::Missing),
+      None => return next.run(req).await,
+    };
+
+    match self.verifier.verify(credentials).await {
+      Ok(principal) => {
+        req.extensions_mut().insert(principal);
+        next.run(req).await
+      }
+      Err(err) => self.reject(err),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::Config;
+  use crate::http::Method;
+  use crate::router::{Route, Router};
+
+  #[test]
+  fn parse() {
+    assert_eq!(
+      Credentials::parse("Basic ZmVycmlzOmh1bnRlcjI="),
+      Ok(Credentials::Basic {
+        username: "ferris".to_owned(),
+        password: "hunter2".to_owned(),
+      })
+    );
+    assert_eq!(
+      Credentials::parse("bearer abc"),
+      Ok(Credentials::Bearer("abc".to_owned()))
+    );
+    assert_eq!(Credentials::parse("Basic !!"), Err(AuthError::Malformed));
+    assert_eq!(
+      Credentials::parse("Digest abc"),
+      Err(AuthError::UnsupportedScheme)
+    );
+    assert_eq!(Credentials::parse("Bearer"), Err(AuthError::Malformed));
+  }
+
+  #[tokio::test]
+  async fn authenticate() {
+    struct User(String);
+
+    let mut router = Router::default();
+    let handler = |req: Request| async move {
+      let user = req.extensions().get::<User>().map(|user| user.0.clone());
+      Response::new(user.unwrap_or_default())
+    };
+    router.route(Route::new(Method::GET, "/public", handler));
+    router.route(Route::new(Method::GET, "/private", handler).auth(AuthRequirement::Required));
+    router.middleware(
+      Authenticate::new(|credentials| async move {
+        match credentials {
+          Credentials::Basic { username, password } if password == "hunter2" => {
+            Some(User(username))
+          }
+          _ => None,
+        }
+      })
+      .schemes(vec![Scheme::Basic]),
+    );
+
+    let config = Config::default();
+    let get = |path: &str, auth: Option<&str>| {
+      let mut req = hyper::Request::builder().uri(path);
+      if let Some(auth) = auth {
+        req = req.header(header::AUTHORIZATION, auth);
+      }
+      router.serve(req.body(hyper::Body::empty()).unwrap().into(), &config)
+    };
+
+    assert_eq!(get("/public", None).await.unwrap().status(), StatusCode::OK);
+
+    let res = get("/private", None).await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+      res.headers()[header::WWW_AUTHENTICATE],
+      r#"Basic realm="turbofish", charset="UTF-8""#
+    );
+
+    let res = get("/private", Some("Basic ZmVycmlzOmh1bnRlcjI="))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // credentials are checked even when optional
+    let res = get("/public", Some("Basic ZmVycmlzOndyb25n"))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = get("/public", Some("Bearer abc")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = get("/private", Some("Basic")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+  }
+}
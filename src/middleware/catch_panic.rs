@@ -0,0 +1,197 @@
+use crate::http::{Body, Method, Request, Response, StatusCode};
+use crate::middleware::{Middleware, Next};
+use crate::router::Route;
+use futures::FutureExt;
+use std::any::Any;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+/// A panic caught while handling a request.
+#[derive(Debug)]
+pub struct Panic {
+  /// The panic message, if the payload was a string.
+  pub message: Option<String>,
+  pub method: Method,
+  pub path: String,
+  /// The pattern of the matched route, ex: `/users/:id`.
+  pub route: Option<&'static str>,
+}
+
+/// Catches panics in the middleware stack and the matched route, answering
+/// with a `500 Internal Server Error` instead of dropping the connection.
+///
+/// Panics are logged along with their route, and passed to the hook set
+/// with [`on_panic`](CatchPanic::on_panic) for error reporting. The router
+/// catches panics with a default `CatchPanic`, which can be replaced with
+/// [`Router::catch_panic`](crate::router::Router::catch_panic). Added as
+/// middleware, it only catches panics in the rest of the stack.
+///
+/// ```rust
+/// use turbofish::middleware::CatchPanic;
+/// use turbofish::router::Router;
+///
+/// let mut router = Router::default();
+/// router.catch_panic(CatchPanic::new().on_panic(|panic| {
+///   // report the panic
+/// }));
+/// ```
+pub struct CatchPanic {
+  response: Box<dyn Fn(&Panic) -> Response + Send + Sync>,
+  hook: Option<Hook>,
+}
+
+type Hook = Box<dyn Fn(&Panic) + Send + Sync>;
+
+impl Default for CatchPanic {
+  fn default() -> Self {
+    Self {
+      response: Box::new(|_| {
+        Response::builder()
+          .status(StatusCode::INTERNAL_SERVER_ERROR)
+          .body(Body::empty())
+          .unwrap()
+      }),
+      hook: None,
+    }
+  }
+}
+
+impl CatchPanic {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Sets the response sent when a panic is caught (default is an empty
+  /// `500 Internal Server Error`).
+  pub fn response(mut self, f: impl Fn(&Panic) -> Response + Send + Sync + 'static) -> Self {
+    self.response = Box::new(f);
+    self
+  }
+
+  /// Sets a hook that is called with every panic caught, ex: to report it
+  /// to an error tracking service.
+  pub fn on_panic(mut self, f: impl Fn(&Panic) + Send + Sync + 'static) -> Self {
+    self.hook = Some(Box::new(f));
+    self
+  }
+}
+
+#[crate::async_trait]
+impl Middleware for CatchPanic {
+  async fn call(&self, req: Request, next: Next<'_>) -> Response {
+    self.run(req, next).await
+  }
+}
+
+impl CatchPanic {
+  /// Runs the rest of the stack, catching any panic.
+  pub(crate) async fn run(&self, req: Request, next: Next<'_>) -> Response {
+    let method = req.method().clone();
+    let path = req.path().to_owned();
+    let route = req
+      .extensions()
+      .get::<Arc<Route>>()
+      .map(|route| route.path());
+
+    match AssertUnwindSafe(next.run(req)).catch_unwind().await {
+      Ok(res) => res,
+      Err(payload) => {
+        let panic = Panic {
+          message: message(payload),
+          method,
+          path,
+          route,
+        };
+
+        log::error!(
+          "panicked while handling `{} {}` (route: {}): {}",
+          panic.method,
+          panic.path,
+          panic.route.unwrap_or("none"),
+          panic.message.as_deref().unwrap_or("Box<Any>"),
+        );
+
+        if let Some(hook) = &self.hook {
+          hook(&panic);
+        }
+
+        (self.response)(&panic)
+      }
+    }
+  }
+}
+
+fn message(payload: Box<dyn Any + Send>) -> Option<String> {
+  match payload.downcast::<String>() {
+    Ok(message) => Some(*message),
+    Err(payload) => payload.downcast_ref::<&str>().map(|s| s.to_string()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::Config;
+  use crate::router::Router;
+  use std::sync::Mutex;
+
+  #[tokio::test]
+  async fn catch_panic() {
+    let panics = Arc::new(Mutex::new(Vec::new()));
+
+    let mut router = Router::default();
+    router.route(Route::new(Method::GET, "/users/:id", |_: Request| async {
+      panic!("user not found");
+    }));
+    router.middleware(CatchPanic::new().on_panic({
+      let panics = panics.clone();
+      move |panic| {
+        panics
+          .lock()
+          .unwrap()
+          .push((panic.message.clone(), panic.route));
+      }
+    }));
+
+    let req = hyper::Request::builder()
+      .uri("/users/1")
+      .body(hyper::Body::empty())
+      .unwrap();
+
+    let res = router.serve(req.into(), &Config::default()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(
+      *panics.lock().unwrap(),
+      vec![(Some("user not found".to_string()), Some("/users/:id"))]
+    );
+  }
+
+  #[tokio::test]
+  async fn by_default() {
+    let mut router = Router::default();
+    router.route(Route::new(Method::GET, "/", |_: Request| async {
+      panic!("oops");
+    }));
+
+    let req = hyper::Request::builder()
+      .uri("/")
+      .body(hyper::Body::empty())
+      .unwrap();
+
+    let res = router.serve(req.into(), &Config::default()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    router.catch_panic(
+      CatchPanic::new().response(|panic| Response::new(panic.message.clone().unwrap())),
+    );
+
+    let req = hyper::Request::builder()
+      .uri("/")
+      .body(hyper::Body::empty())
+      .unwrap();
+
+    let res = router.serve(req.into(), &Config::default()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.into_body().bytes().await.unwrap(), "oops");
+  }
+}
@@ -0,0 +1,583 @@
+use crate::http::{header, Body, HeaderMap, HeaderValue, Method, Request, Response, StatusCode};
+use crate::middleware::{Middleware, Next};
+use std::future::Future;
+use std::pin::Pin;
+use std::time::SystemTime;
+
+/// Conditional request middleware.
+///
+/// Responses to `GET` and `HEAD` requests with a [`Body::Once`] body are
+/// given a strong `ETag` derived from a SHA-256 hash of the body, unless
+/// the handler set one. Conditional `GET` and `HEAD` requests are answered with
+/// `304 Not Modified` when `If-None-Match` or `If-Modified-Since` match the
+/// response.
+///
+/// Conditional writes (`If-Match`, `If-Unmodified-Since` and
+/// `If-None-Match` on other methods) are evaluated against the validators
+/// returned by the route's [`Route::validators`](crate::router::Route::validators)
+/// lookup, and rejected with `412 Precondition Failed` before the handler
+/// runs. Routes without a lookup are run as is, leaving the conditions to
+/// the handler.
+///
+/// ```rust
+/// use turbofish::http::{Method, Request, Response};
+/// use turbofish::middleware::conditional::Validators;
+/// use turbofish::router::{Params, Route, Router};
+///
+/// # async fn version(_: &str) -> Option<u64> { Some(1) }
+/// async fn update(_: Request) -> Response {
+///   Response::no_content()
+/// }
+///
+/// let mut router = Router::default();
+/// router.route(Route::new(Method::PUT, "/posts/:id", update).validators(|req: &Request| {
+///   let params = req.extensions().get::<Params>().unwrap();
+///   let id = params.by_name("id").unwrap().to_owned();
+///   async move {
+///     // `None` if the post doesn't exist
+///     let version = version(&id).await?;
+///     Some(Validators::new().etag(&version.to_string()))
+///   }
+/// }));
+/// ```
+///
+/// The lookup runs before the handler, so writes that race with each other
+/// can both pass it. Handlers that need the check to be atomic should also
+/// compare the version in the write itself.
+pub struct Conditional {
+  etags: bool,
+}
+
+impl Default for Conditional {
+  fn default() -> Self {
+    Self { etags: true }
+  }
+}
+
+impl Conditional {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Sets whether ETags are generated for responses without one (default
+  /// is `true`).
+  pub fn etags(mut self, etags: bool) -> Self {
+    self.etags = etags;
+    self
+  }
+
+  /// Returns the validators of a response, generating an ETag if allowed.
+  fn response_validators(&self, res: &Response) -> Validators {
+    let etag = match res
+      .headers()
+      .get(header::ETAG)
+      .and_then(|tag| tag.to_str().ok())
+    {
+      Some(tag) => EntityTag::parse(tag),
+      None => match res.body() {
+        Body::Once(bytes) if self.etags => Some(EntityTag::hash(bytes)),
+        _ => None,
+      },
+    };
+
+    Validators {
+      etag,
+      last_modified: res
+        .headers()
+        .get(header::LAST_MODIFIED)
+        .and_then(|date| date.to_str().ok())
+        .and_then(|date| httpdate::parse_http_date(date).ok()),
+    }
+  }
+}
+
+#[crate::async_trait]
+impl Middleware for Conditional {
+  async fn call(&self, req: Request, next: Next<'_>) -> Response {
+    let conditions = Conditions::new(req.headers());
+
+    if !matches!(*req.method(), Method::GET | Method::HEAD) {
+      if conditions.is_empty() {
+        return next.run(req).await;
+      }
+
+      let current = match req
+        .route()
+        .and_then(|route| route.extensions().get::<Validator>())
+      {
+        Some(validator) => (validator.lookup)(&req),
+        None => return next.run(req).await,
+      };
+
+      if conditions.evaluate(current.await.as_ref(), false) != Outcome::Pass {
+        return status(StatusCode::PRECONDITION_FAILED);
+      }
+
+      return next.run(req).await;
+    }
+
+    let mut res = next.run(req).await;
+
+    // conditions only apply to successful responses
+    if !res.status().is_success() {
+      return res;
+    }
+
+    let validators = self.response_validators(&res);
+    if let Some(tag) = &validators.etag {
+      if !res.headers().contains_key(header::ETAG) {
+        let value = HeaderValue::from_str(&tag.to_string()).expect("generated tags are valid");
+        res.headers_mut().insert(header::ETAG, value);
+      }
+    }
+
+    match conditions.evaluate(Some(&validators), true) {
+      Outcome::Pass => res,
+      Outcome::NotModified => not_modified(res),
+      Outcome::Failed => status(StatusCode::PRECONDITION_FAILED),
+    }
+  }
+}
+
+/// Strips a response down to a `304 Not Modified`, keeping the headers a
+/// cache needs to update its stored response.
+fn not_modified(res: Response) -> Response {
+  let mut not_modified = status(StatusCode::NOT_MODIFIED);
+
+  for name in &[
+    header::CACHE_CONTROL,
+    header::CONTENT_LOCATION,
+    header::DATE,
+    header::ETAG,
+    header::EXPIRES,
+    header::LAST_MODIFIED,
+    header::VARY,
+  ] {
+    for value in res.headers().get_all(name) {
+      not_modified.headers_mut().append(name, value.clone());
+    }
+  }
+
+  not_modified
+}
+
+fn status(status: StatusCode) -> Response {
+  Response::builder()
+    .status(status)
+    .body(Body::empty())
+    .unwrap()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct EntityTag {
+  weak: bool,
+  tag: String,
+}
+
+impl EntityTag {
+  fn parse(s: &str) -> Option<Self> {
+    let s = s.trim();
+    let (weak, s) = match s.strip_prefix("W/") {
+      Some(s) => (true, s),
+      None => (false, s),
+    };
+
+    if s.len() < 2 || !s.starts_with('"') || !s.ends_with('"') {
+      return None;
+    }
+
+    Some(Self {
+      weak,
+      tag: s[1..s.len() - 1].to_owned(),
+    })
+  }
+
+  /// A strong tag derived from the length and SHA-256 hash of `bytes`.
+  /// The hash is collision resistant, so equal tags mean byte-for-byte
+  /// equal bodies, as strong comparison requires.
+  fn hash(bytes: &[u8]) -> Self {
+    let hash = ring::digest::digest(&ring::digest::SHA256, bytes);
+
+    Self {
+      weak: false,
+      tag: format!(
+        "{:x}-{}",
+        bytes.len(),
+        base64::encode_config(hash.as_ref(), base64::URL_SAFE_NO_PAD)
+      ),
+    }
+  }
+}
+
+impl std::fmt::Display for EntityTag {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if self.weak {
+      f.write_str("W/")?;
+    }
+    write!(f, "\"{}\"", self.tag)
+  }
+}
+
+type Lookup =
+  dyn Fn(&Request) -> Pin<Box<dyn Future<Output = Option<Validators>> + Send>> + Send + Sync;
+
+/// Looks up the validators of the current representation of a route's
+/// resource, see [`Route::validators`](crate::router::Route::validators).
+pub(crate) struct Validator {
+  lookup: Box<Lookup>,
+}
+
+impl Validator {
+  pub(crate) fn new<F, Fut>(lookup: F) -> Self
+  where
+    F: Fn(&Request) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Option<Validators>> + Send + 'static,
+  {
+    Self {
+      lookup: Box::new(move |req| Box::pin(lookup(req))),
+    }
+  }
+}
+
+/// The validators of the current representation of a resource.
+#[derive(Clone, Debug, Default)]
+pub struct Validators {
+  etag: Option<EntityTag>,
+  last_modified: Option<SystemTime>,
+}
+
+impl Validators {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Sets the entity tag, as sent in the `ETag` header, ex: `"v2"` or
+  /// `W/"v2"`. Unquoted tags are quoted, so `v2` is the same as `"v2"`.
+  pub fn etag(mut self, etag: &str) -> Self {
+    let etag = EntityTag::parse(etag).unwrap_or_else(|| EntityTag {
+      weak: false,
+      tag: etag.trim().replace('"', ""),
+    });
+    self.etag = Some(etag);
+    self
+  }
+
+  /// Sets the entity tag the middleware generates for `body`, for
+  /// resources whose `GET` route relies on generated ETags.
+  pub fn body(mut self, body: &[u8]) -> Self {
+    self.etag = Some(EntityTag::hash(body));
+    self
+  }
+
+  pub fn last_modified(mut self, last_modified: SystemTime) -> Self {
+    self.last_modified = Some(last_modified);
+    self
+  }
+}
+
+/// A list of entity tags, or `*`.
+enum Tags {
+  Any,
+  List(Vec<EntityTag>),
+}
+
+impl Tags {
+  fn parse(headers: &HeaderMap, name: header::HeaderName) -> Option<Self> {
+    let mut tags = Vec::new();
+
+    for value in headers.get_all(name) {
+      let value = value.to_str().ok()?;
+      if value.trim() == "*" {
+        return Some(Tags::Any);
+      }
+      tags.extend(value.split(',').filter_map(EntityTag::parse));
+    }
+
+    if tags.is_empty() {
+      None
+    } else {
+      Some(Tags::List(tags))
+    }
+  }
+
+  /// Compares the tags to `current` with the strong or weak comparison of
+  /// RFC 7232, section 2.3.2.
+  fn matches(&self, current: Option<&EntityTag>, strong: bool) -> bool {
+    match (self, current) {
+      (Tags::Any, current) => current.is_some(),
+      (Tags::List(_), None) => false,
+      (Tags::List(tags), Some(current)) => tags
+        .iter()
+        .any(|tag| tag.tag == current.tag && (!strong || (!tag.weak && !current.weak))),
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+  Pass,
+  NotModified,
+  Failed,
+}
+
+struct Conditions {
+  if_match: Option<Tags>,
+  if_unmodified_since: Option<SystemTime>,
+  if_none_match: Option<Tags>,
+  if_modified_since: Option<SystemTime>,
+}
+
+impl Conditions {
+  fn new(headers: &HeaderMap) -> Self {
+    let date = |name| {
+      headers
+        .get(name)
+        .and_then(|date: &HeaderValue| date.to_str().ok())
+        .and_then(|date| httpdate::parse_http_date(date).ok())
+    };
+
+    Self {
+      if_match: Tags::parse(headers, header::IF_MATCH),
+      if_unmodified_since: date(header::IF_UNMODIFIED_SINCE),
+      if_none_match: Tags::parse(headers, header::IF_NONE_MATCH),
+      if_modified_since: date(header::IF_MODIFIED_SINCE),
+    }
+  }
+
+  fn is_empty(&self) -> bool {
+    self.if_match.is_none()
+      && self.if_unmodified_since.is_none()
+      && self.if_none_match.is_none()
+      && self.if_modified_since.is_none()
+  }
+
+  /// Evaluates the conditions in the order of RFC 7232, section 6.
+  /// `current` is `None` if the resource has no current representation.
+  fn evaluate(&self, current: Option<&Validators>, safe: bool) -> Outcome {
+    let etag = current.and_then(|current| current.etag.as_ref());
+    let last_modified = current.and_then(|current| current.last_modified);
+
+    if let Some(tags) = &self.if_match {
+      let exists = current.is_some();
+      if !(exists && (matches!(tags, Tags::Any) || tags.matches(etag, true))) {
+        return Outcome::Failed;
+      }
+    } else if let (Some(since), Some(modified)) = (self.if_unmodified_since, last_modified) {
+      if modified > since {
+        return Outcome::Failed;
+      }
+    }
+
+    if let Some(tags) = &self.if_none_match {
+      let matches = match tags {
+        Tags::Any => current.is_some(),
+        tags => tags.matches(etag, false),
+      };
+
+      if matches {
+        return if safe {
+          Outcome::NotModified
+        } else {
+          Outcome::Failed
+        };
+      }
+    } else if let (true, Some(since), Some(modified)) =
+      (safe, self.if_modified_since, last_modified)
+    {
+      if modified <= since {
+        return Outcome::NotModified;
+      }
+    }
+
+    Outcome::Pass
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::Config;
+  use crate::router::{Route, Router};
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::sync::{Arc, Mutex};
+
+  #[tokio::test]
+  async fn conditional() {
+    let post = Arc::new(Mutex::new("hello".to_owned()));
+    let gets = Arc::new(AtomicUsize::new(0));
+    let modified = httpdate::parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT").unwrap();
+
+    let mut router = Router::default();
+    router.route(Route::new(Method::GET, "/post", {
+      let post = post.clone();
+      let gets = gets.clone();
+      move |_: Request| {
+        gets.fetch_add(1, Ordering::SeqCst);
+        let body = post.lock().unwrap().clone();
+        async move {
+          Response::builder()
+            .header(header::LAST_MODIFIED, "Wed, 21 Oct 2015 07:28:00 GMT")
+            .body(body)
+            .unwrap()
+        }
+      }
+    }));
+    router.route(
+      Route::new(Method::PUT, "/post", {
+        let post = post.clone();
+        move |_: Request| {
+          *post.lock().unwrap() = "updated".to_owned();
+          async { Response::new("") }
+        }
+      })
+      .validators({
+        let post = post.clone();
+        move |_: &Request| {
+          let body = post.lock().unwrap().clone();
+          async move {
+            Some(
+              Validators::new()
+                .body(body.as_bytes())
+                .last_modified(modified),
+            )
+          }
+        }
+      }),
+    );
+    router.route(Route::new(Method::DELETE, "/post", |_: Request| async {
+      Response::new("")
+    }));
+    router.middleware(Conditional::new());
+
+    let config = Config::default();
+    let send = |method: Method, headers: Vec<(header::HeaderName, String)>| {
+      let mut req = hyper::Request::builder().method(method).uri("/post");
+      for (name, value) in headers {
+        req = req.header(name, value);
+      }
+      router.serve(req.body(hyper::Body::empty()).unwrap().into(), &config)
+    };
+
+    let res = send(Method::GET, vec![]).await.unwrap();
+    let etag = res.headers()[header::ETAG].to_str().unwrap().to_owned();
+    assert!(etag.starts_with("\"5-"), "{}", etag);
+
+    let res = send(Method::GET, vec![(header::IF_NONE_MATCH, etag.clone())])
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(res.headers()[header::ETAG], etag.as_str());
+
+    let res = send(
+      Method::GET,
+      vec![(header::IF_NONE_MATCH, "\"other\"".into())],
+    )
+    .await
+    .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let since = "Thu, 22 Oct 2015 07:28:00 GMT".to_owned();
+    let res = send(
+      Method::GET,
+      vec![(header::IF_MODIFIED_SINCE, since.clone())],
+    )
+    .await
+    .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+    let res = send(Method::PUT, vec![(header::IF_MATCH, "\"other\"".into())])
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+    assert_eq!(*post.lock().unwrap(), "hello");
+
+    let res = send(Method::PUT, vec![(header::IF_NONE_MATCH, "*".into())])
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+
+    let before = "Tue, 20 Oct 2015 07:28:00 GMT".to_owned();
+    let res = send(Method::PUT, vec![(header::IF_UNMODIFIED_SINCE, before)])
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+
+    let res = send(Method::PUT, vec![(header::IF_MATCH, etag.clone())])
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(*post.lock().unwrap(), "updated");
+
+    // the representation changed
+    let res = send(Method::PUT, vec![(header::IF_MATCH, etag.clone())])
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+
+    // writes never run the `GET` route
+    assert_eq!(gets.load(Ordering::SeqCst), 4);
+
+    // routes without a lookup leave the conditions to the handler
+    let res = send(Method::DELETE, vec![(header::IF_MATCH, etag)])
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+  }
+
+  #[test]
+  fn comparison() {
+    let evaluate = |name, value: &str, validators: &Validators| {
+      let mut headers = HeaderMap::new();
+      headers.insert(name, HeaderValue::from_str(value).unwrap());
+      Conditions::new(&headers).evaluate(Some(validators), false)
+    };
+
+    // weak tags only match with the weak comparison of `If-None-Match`
+    let weak = Validators::new().etag("W/\"v1\"");
+    assert_eq!(
+      evaluate(header::IF_MATCH, "W/\"v1\"", &weak),
+      Outcome::Failed
+    );
+    assert_eq!(
+      evaluate(header::IF_NONE_MATCH, "\"v1\"", &weak),
+      Outcome::Failed
+    );
+
+    // unquoted tags are quoted
+    let strong = Validators::new().etag("v1");
+    assert_eq!(evaluate(header::IF_MATCH, "\"v1\"", &strong), Outcome::Pass);
+    assert_eq!(
+      evaluate(header::IF_MATCH, "W/\"v1\"", &strong),
+      Outcome::Failed
+    );
+
+    let body = Validators::new().body(b"hello");
+    let tag = EntityTag::hash(b"hello").to_string();
+    assert!(!tag.starts_with("W/"));
+    assert_eq!(evaluate(header::IF_MATCH, &tag, &body), Outcome::Pass);
+  }
+
+  #[tokio::test]
+  async fn missing_resource() {
+    let mut router = Router::default();
+    router.route(
+      Route::new(Method::PUT, "/post", |_: Request| async {
+        Response::new("")
+      })
+      .validators(|_: &Request| async { None }),
+    );
+    router.middleware(Conditional::new());
+
+    let config = Config::default();
+    let send = |name: header::HeaderName, value: &'static str| {
+      let req = hyper::Request::put("/post").header(name, value);
+      router.serve(req.body(hyper::Body::empty()).unwrap().into(), &config)
+    };
+
+    let res = send(header::IF_NONE_MATCH, "*").await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = send(header::IF_MATCH, "*").await.unwrap();
+    assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+  }
+}
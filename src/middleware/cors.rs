@@ -0,0 +1,447 @@
+use crate::http::{
+  header, Body, HeaderMap, HeaderName, HeaderValue, Method, Request, Response, StatusCode,
+};
+use crate::middleware::{Middleware, Next};
+use std::time::Duration;
+
+/// Cross-Origin Resource Sharing middleware.
+///
+/// Preflight requests are answered with the methods the router has routes
+/// for at the requested path, narrowed down to [`allow_methods`](Cors::allow_methods)
+/// if set. Responses to other requests from an allowed origin are decorated
+/// with the `Access-Control-*` headers.
+///
+/// ```rust
+/// use std::time::Duration;
+/// use turbofish::http::{header, Method};
+/// use turbofish::middleware::Cors;
+///
+/// let cors = Cors::new()
+///   .allow_origin("https://example.com")
+///   .allow_origin("https://*.example.com")
+///   .allow_methods(vec![Method::GET, Method::POST])
+///   .allow_headers(vec![header::CONTENT_TYPE])
+///   .allow_credentials(true)
+///   .max_age(Duration::from_secs(3600));
+/// ```
+pub struct Cors {
+  origins: Vec<Origin>,
+  methods: Option<Vec<Method>>,
+  headers: AllowHeaders,
+  expose_headers: Vec<HeaderName>,
+  credentials: bool,
+  max_age: Option<Duration>,
+}
+
+enum Origin {
+  /// `*`, any origin.
+  Any,
+  /// A single origin, ex: `https://example.com`.
+  Exact(String),
+  /// An origin with a single wildcard, ex: `https://*.example.com`.
+  Wildcard(String, String),
+  /// A user supplied predicate.
+  Predicate(Box<dyn Fn(&str) -> bool + Send + Sync>),
+}
+
+impl Origin {
+  fn matches(&self, origin: &str) -> bool {
+    match self {
+      Origin::Any => true,
+      Origin::Exact(exact) => exact.eq_ignore_ascii_case(origin),
+      Origin::Wildcard(prefix, suffix) => {
+        origin.len() > prefix.len() + suffix.len()
+          && origin.starts_with(prefix.as_str())
+          && origin.ends_with(suffix.as_str())
+      }
+      Origin::Predicate(f) => f(origin),
+    }
+  }
+}
+
+enum AllowHeaders {
+  List(Vec<HeaderName>),
+  /// Echo the `Access-Control-Request-Headers` of the preflight.
+  Mirror,
+}
+
+impl Default for Cors {
+  fn default() -> Self {
+    Self {
+      origins: Vec::new(),
+      methods: None,
+      headers: AllowHeaders::List(Vec::new()),
+      expose_headers: Vec::new(),
+      credentials: false,
+      max_age: None,
+    }
+  }
+}
+
+impl Cors {
+  /// Creates a CORS middleware that allows no origins.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Allows requests from the given origin. The origin may be `*` to allow
+  /// any origin, or contain a single `*` wildcard, ex: `https://*.example.com`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the origin is `*` and credentials are allowed.
+  pub fn allow_origin(mut self, origin: &str) -> Self {
+    let origin = match origin.find('*') {
+      Some(_) if origin == "*" => Origin::Any,
+      Some(i) => Origin::Wildcard(origin[..i].to_string(), origin[i + 1..].to_string()),
+      None => Origin::Exact(origin.to_string()),
+    };
+    self.origins.push(origin);
+    self.check_credentials();
+    self
+  }
+
+  /// Allows requests from any origin for which the predicate returns `true`.
+  pub fn allow_origin_fn(mut self, f: impl Fn(&str) -> bool + Send + Sync + 'static) -> Self {
+    self.origins.push(Origin::Predicate(Box::new(f)));
+    self
+  }
+
+  /// Sets the methods allowed in preflight responses. Defaults to every
+  /// method the router has a route for at the requested path.
+  pub fn allow_methods(mut self, methods: impl IntoIterator<Item = Method>) -> Self {
+    self.methods = Some(methods.into_iter().collect());
+    self
+  }
+
+  /// Sets the request headers allowed in preflight responses.
+  pub fn allow_headers(mut self, headers: impl IntoIterator<Item = HeaderName>) -> Self {
+    self.headers = AllowHeaders::List(headers.into_iter().collect());
+    self
+  }
+
+  /// Allows any request headers by echoing the headers requested by the
+  /// preflight.
+  pub fn allow_any_header(mut self) -> Self {
+    self.headers = AllowHeaders::Mirror;
+    self
+  }
+
+  /// Sets the response headers exposed to the client.
+  pub fn expose_headers(mut self, headers: impl IntoIterator<Item = HeaderName>) -> Self {
+    self.expose_headers = headers.into_iter().collect();
+    self
+  }
+
+  /// Sets whether the response may be exposed when the request's credentials
+  /// mode is `include` (default is false).
+  ///
+  /// # Panics
+  ///
+  /// Panics if `allow` is true and any origin is allowed with `*`.
+  pub fn allow_credentials(mut self, allow: bool) -> Self {
+    self.credentials = allow;
+    self.check_credentials();
+    self
+  }
+
+  /// Sets how long the results of a preflight request may be cached.
+  pub fn max_age(mut self, max_age: Duration) -> Self {
+    self.max_age = Some(max_age);
+    self
+  }
+
+  // browsers reject credentialed responses with a wildcard origin, and
+  // echoing every origin instead would expose credentialed responses to
+  // any site, so that has to be asked for explicitly with a predicate
+  fn check_credentials(&self) {
+    assert!(
+      !(self.credentials && self.allows_any()),
+      "CORS cannot allow credentials for any origin with `*`, use `allow_origin_fn` to allow credentialed requests from any origin"
+    );
+  }
+
+  fn allows_any(&self) -> bool {
+    self.origins.iter().any(|o| matches!(o, Origin::Any))
+  }
+
+  fn allows(&self, origin: &HeaderValue) -> bool {
+    match origin.to_str() {
+      Ok(origin) => self.origins.iter().any(|o| o.matches(origin)),
+      Err(_) => false,
+    }
+  }
+
+  /// Sets the headers shared by preflight and actual responses.
+  fn decorate(&self, origin: &HeaderValue, headers: &mut HeaderMap) {
+    if self.allows_any() {
+      headers.insert(
+        header::ACCESS_CONTROL_ALLOW_ORIGIN,
+        HeaderValue::from_static("*"),
+      );
+    } else {
+      headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+    }
+
+    if self.credentials {
+      headers.insert(
+        header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+        HeaderValue::from_static("true"),
+      );
+    }
+  }
+
+  async fn preflight(&self, req: Request, next: Next<'_>) -> Response {
+    let allowed = next.router().allowed(req.path());
+
+    // let the router respond with a 404
+    if allowed.is_empty() {
+      return next.run(req).await;
+    }
+
+    let methods = allowed
+      .into_iter()
+      .filter(|allowed| match &self.methods {
+        Some(methods) => methods.iter().any(|m| m == allowed),
+        None => true,
+      })
+      .collect::<Vec<_>>()
+      .join(", ");
+
+    let mut res = Response::builder()
+      .status(StatusCode::NO_CONTENT)
+      .header(header::ACCESS_CONTROL_ALLOW_METHODS, methods)
+      .body(Body::empty())
+      .unwrap();
+
+    let headers = res.headers_mut();
+    self.decorate(&req.headers()[header::ORIGIN], headers);
+
+    match &self.headers {
+      AllowHeaders::Mirror => {
+        if let Some(requested) = req.headers().get(header::ACCESS_CONTROL_REQUEST_HEADERS) {
+          headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, requested.clone());
+        }
+      }
+      AllowHeaders::List(list) if !list.is_empty() => {
+        headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, join(list));
+      }
+      AllowHeaders::List(_) => {}
+    }
+
+    if let Some(max_age) = self.max_age {
+      headers.insert(header::ACCESS_CONTROL_MAX_AGE, max_age.as_secs().into());
+    }
+
+    res
+  }
+}
+
+#[crate::async_trait]
+impl Middleware for Cors {
+  async fn call(&self, req: Request, next: Next<'_>) -> Response {
+    let mut res = self.respond(req, next).await;
+
+    // unless every origin is allowed, the response depends on the origin,
+    // including when it is answered without CORS headers
+    if !self.allows_any() {
+      res
+        .headers_mut()
+        .append(header::VARY, HeaderValue::from_static("Origin"));
+    }
+
+    res
+  }
+}
+
+impl Cors {
+  async fn respond(&self, req: Request, next: Next<'_>) -> Response {
+    let origin = match req.headers().get(header::ORIGIN) {
+      Some(origin) if self.allows(origin) => origin.clone(),
+      _ => return next.run(req).await,
+    };
+
+    if req.method() == Method::OPTIONS
+      && req
+        .headers()
+        .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD)
+    {
+      return self.preflight(req, next).await;
+    }
+
+    let mut res = next.run(req).await;
+    let headers = res.headers_mut();
+    self.decorate(&origin, headers);
+
+    if !self.expose_headers.is_empty() {
+      headers.insert(
+        header::ACCESS_CONTROL_EXPOSE_HEADERS,
+        join(&self.expose_headers),
+      );
+    }
+
+    res
+  }
+}
+
+fn join(headers: &[HeaderName]) -> HeaderValue {
+  let joined = headers
+    .iter()
+    .map(HeaderName::as_str)
+    .collect::<Vec<_>>()
+    .join(", ");
+
+  HeaderValue::from_str(&joined).expect("header names are valid header values")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::Config;
+  use crate::router::{Route, Router};
+
+  fn router(cors: Cors) -> Router {
+    let mut router = Router::default();
+    router.route(Route::new(Method::GET, "/users", |_: Request| async {
+      Response::new("users")
+    }));
+    router.route(Route::new(Method::POST, "/users", |_: Request| async {
+      Response::new("created")
+    }));
+    router.middleware(cors);
+    router
+  }
+
+  fn request(method: Method, uri: &str, headers: &[(&str, &str)]) -> Request {
+    let mut req = hyper::Request::builder().method(method).uri(uri);
+    for (key, value) in headers {
+      req = req.header(*key, *value);
+    }
+    req.body(hyper::Body::empty()).unwrap().into()
+  }
+
+  #[test]
+  fn origins() {
+    let cors = Cors::new()
+      .allow_origin("https://example.com")
+      .allow_origin("https://*.turbofish.rs")
+      .allow_origin_fn(|origin| origin.ends_with(":8080"));
+
+    let allows = |origin| cors.allows(&HeaderValue::from_static(origin));
+    assert!(allows("https://example.com"));
+    assert!(allows("https://api.turbofish.rs"));
+    assert!(allows("http://localhost:8080"));
+    assert!(!allows("https://turbofish.rs"));
+    assert!(!allows("https://example.com.evil.com"));
+    assert!(!allows("http://localhost:3000"));
+  }
+
+  #[tokio::test]
+  async fn preflight() {
+    let router = router(
+      Cors::new()
+        .allow_origin("https://example.com")
+        .allow_any_header()
+        .max_age(Duration::from_secs(60)),
+    );
+
+    let req = request(
+      Method::OPTIONS,
+      "/users",
+      &[
+        ("Origin", "https://example.com"),
+        ("Access-Control-Request-Method", "POST"),
+        ("Access-Control-Request-Headers", "content-type"),
+      ],
+    );
+
+    let res = router.serve(req, &Config::default()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    let headers = res.headers();
+    assert_eq!(
+      headers["access-control-allow-origin"],
+      "https://example.com"
+    );
+    assert_eq!(
+      headers["access-control-allow-methods"],
+      "GET, OPTIONS, POST"
+    );
+    assert_eq!(headers["access-control-allow-headers"], "content-type");
+    assert_eq!(headers["access-control-max-age"], "60");
+    assert_eq!(headers["vary"], "Origin");
+  }
+
+  #[tokio::test]
+  async fn preflight_methods() {
+    let router = router(
+      Cors::new()
+        .allow_origin("*")
+        .allow_methods(vec![Method::GET]),
+    );
+
+    let req = request(
+      Method::OPTIONS,
+      "/users",
+      &[
+        ("Origin", "https://a.com"),
+        ("Access-Control-Request-Method", "GET"),
+      ],
+    );
+
+    let res = router.serve(req, &Config::default()).await.unwrap();
+    assert_eq!(res.headers()["access-control-allow-origin"], "*");
+    assert_eq!(res.headers()["access-control-allow-methods"], "GET");
+  }
+
+  #[tokio::test]
+  async fn actual_request() {
+    let router = router(
+      Cors::new()
+        .allow_origin("https://example.com")
+        .allow_credentials(true)
+        .expose_headers(vec![header::ETAG]),
+    );
+
+    let req = request(Method::GET, "/users", &[("Origin", "https://example.com")]);
+    let res = router.serve(req, &Config::default()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let headers = res.headers();
+    assert_eq!(
+      headers["access-control-allow-origin"],
+      "https://example.com"
+    );
+    assert_eq!(headers["access-control-allow-credentials"], "true");
+    assert_eq!(headers["access-control-expose-headers"], "etag");
+
+    let req = request(Method::GET, "/users", &[("Origin", "https://evil.com")]);
+    let res = router.serve(req, &Config::default()).await.unwrap();
+    assert!(!res.headers().contains_key("access-control-allow-origin"));
+    assert_eq!(res.headers()["vary"], "Origin");
+
+    let req = request(Method::GET, "/users", &[]);
+    let res = router.serve(req, &Config::default()).await.unwrap();
+    assert_eq!(res.headers()["vary"], "Origin");
+  }
+
+  #[tokio::test]
+  async fn any_origin() {
+    let router = router(Cors::new().allow_origin("*"));
+
+    let req = request(Method::GET, "/users", &[("Origin", "https://a.com")]);
+    let res = router.serve(req, &Config::default()).await.unwrap();
+    assert_eq!(res.headers()["access-control-allow-origin"], "*");
+    assert!(!res.headers().contains_key("vary"));
+  }
+
+  #[test]
+  #[should_panic(expected = "cannot allow credentials for any origin")]
+  fn any_origin_with_credentials() {
+    Cors::new().allow_credentials(true).allow_origin("*");
+  }
+
+  #[test]
+  #[should_panic(expected = "cannot allow credentials for any origin")]
+  fn credentials_with_any_origin() {
+    Cors::new().allow_origin("*").allow_credentials(true);
+  }
+}
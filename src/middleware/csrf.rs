@@ -0,0 +1,509 @@
+use crate::http::{Body, BodyError, Cookie, HeaderName, Request, Response, SameSite, StatusCode};
+use crate::middleware::{Middleware, Next};
+use rand::RngCore;
+use std::error::Error;
+use std::fmt;
+use subtle::ConstantTimeEq;
+
+const SECRET_LEN: usize = 32;
+
+/// The CSRF token of the current request, to be embedded in forms or sent
+/// back in a header by scripts.
+///
+/// A new token is generated for every request to protect against the
+/// BREACH attack, but every token stays valid for as long as the CSRF
+/// cookie does.
+///
+/// ```rust
+/// use turbofish::http::{Request, Response};
+/// use turbofish::middleware::csrf::CsrfToken;
+///
+/// async fn form(req: Request) -> Response {
+///   let token = req.extensions().get::<CsrfToken>().unwrap();
+///   Response::new(format!(
+///     r#"<form method="post"><input type="hidden" name="_csrf" value="{}"></form>"#,
+///     token.as_str()
+///   ))
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct CsrfToken(String);
+
+impl CsrfToken {
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+}
+
+/// The reason a request was rejected by the [`Csrf`] middleware.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CsrfError {
+  /// The request did not carry a valid CSRF cookie.
+  MissingCookie,
+  /// The request did not carry a token in the header or form field.
+  MissingToken,
+  /// The token does not match the CSRF cookie.
+  InvalidToken,
+}
+
+impl fmt::Display for CsrfError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      CsrfError::MissingCookie => f.write_str("missing CSRF cookie"),
+      CsrfError::MissingToken => f.write_str("missing CSRF token"),
+      CsrfError::InvalidToken => f.write_str("invalid CSRF token"),
+    }
+  }
+}
+
+impl Error for CsrfError {}
+
+/// Cross-Site Request Forgery protection middleware.
+///
+/// A random secret is stored in a cookie, and requests with unsafe methods
+/// (anything but `GET`, `HEAD`, `OPTIONS` and `TRACE`) must send back a
+/// [`CsrfToken`] derived from it, either in the `X-CSRF-Token` header or
+/// in the `_csrf` field of a url-encoded form. Requests that don't match a
+/// route are left to the router, so they are still answered with
+/// `404 Not Found` or `405 Method Not Allowed`.
+///
+/// By default the cookie is signed with the
+/// [`secret_key`](crate::config::Config::secret_key) and hidden from
+/// scripts. Without a secret key, requests are answered with
+/// `500 Internal Server Error`. With [`double_submit`](Csrf::double_submit),
+/// the cookie is readable by scripts instead, which send its value back in
+/// the header.
+///
+/// ```rust
+/// use turbofish::middleware::Csrf;
+/// use turbofish::router::Router;
+///
+/// let mut router = Router::default();
+/// router.middleware(Csrf::new().exempt_prefix("/webhooks/"));
+/// ```
+pub struct Csrf {
+  cookie_name: String,
+  header_name: HeaderName,
+  field_name: String,
+  double_submit: bool,
+  secure: bool,
+  exempt_routes: Vec<&'static str>,
+  exempt_prefixes: Vec<String>,
+  rejection: Box<dyn Fn(CsrfError) -> Response + Send + Sync>,
+}
+
+impl Default for Csrf {
+  fn default() -> Self {
+    Self {
+      cookie_name: "csrf".to_owned(),
+      header_name: HeaderName::from_static("x-csrf-token"),
+      field_name: "_csrf".to_owned(),
+      double_submit: false,
+      secure: true,
+      exempt_routes: Vec::new(),
+      exempt_prefixes: Vec::new(),
+      rejection: Box::new(|_| {
+        Response::builder()
+          .status(StatusCode::FORBIDDEN)
+          .body(Body::empty())
+          .unwrap()
+      }),
+    }
+  }
+}
+
+impl Csrf {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Uses the double-submit cookie pattern: the secret is stored in a plain
+  /// cookie readable by scripts, so no secret key is needed.
+  pub fn double_submit(mut self) -> Self {
+    self.double_submit = true;
+    self
+  }
+
+  /// Sets the name of the CSRF cookie (default is `csrf`).
+  pub fn cookie_name(mut self, name: impl Into<String>) -> Self {
+    self.cookie_name = name.into();
+    self
+  }
+
+  /// Sets the header the token is read from (default is `x-csrf-token`).
+  pub fn header_name(mut self, name: HeaderName) -> Self {
+    self.header_name = name;
+    self
+  }
+
+  /// Sets the form field the token is read from (default is `_csrf`).
+  pub fn field_name(mut self, name: impl Into<String>) -> Self {
+    self.field_name = name.into();
+    self
+  }
+
+  /// Sets whether the CSRF cookie is only sent over HTTPS (default is
+  /// `true`).
+  pub fn secure(mut self, secure: bool) -> Self {
+    self.secure = secure;
+    self
+  }
+
+  /// Exempts the route with the given [name](crate::router::Route::named).
+  pub fn exempt_route(mut self, name: &'static str) -> Self {
+    self.exempt_routes.push(name);
+    self
+  }
+
+  /// Exempts every request whose path starts with `prefix`.
+  pub fn exempt_prefix(mut self, prefix: impl Into<String>) -> Self {
+    self.exempt_prefixes.push(prefix.into());
+    self
+  }
+
+  /// Sets the response sent when a request is rejected (default is an
+  /// empty `403 Forbidden`).
+  pub fn rejection(mut self, f: impl Fn(CsrfError) -> Response + Send + Sync + 'static) -> Self {
+    self.rejection = Box::new(f);
+    self
+  }
+
+  fn is_exempt(&self, req: &Request) -> bool {
+    let route = match req.route() {
+      Some(route) => route,
+      None => return true,
+    };
+
+    req.method().is_safe()
+      || self.exempt_routes.contains(&route.name())
+      || self
+        .exempt_prefixes
+        .iter()
+        .any(|prefix| req.path().starts_with(prefix.as_str()))
+  }
+
+  fn secret(&self, req: &Request) -> Option<Vec<u8>> {
+    let cookie = if self.double_submit {
+      req.cookies().get(&self.cookie_name)
+    } else {
+      req.cookies().signed()?.get(&self.cookie_name)
+    };
+
+    cookie
+      .and_then(|cookie| decode_hex(cookie.value()))
+      .filter(|secret| secret.len() == SECRET_LEN)
+  }
+
+  fn set_secret(&self, req: &Request, secret: &[u8]) {
+    let cookie = Cookie::build(self.cookie_name.clone(), encode_hex(secret))
+      .path("/")
+      .http_only(!self.double_submit)
+      .secure(self.secure)
+      .same_site(SameSite::Lax)
+      .finish();
+
+    if self.double_submit {
+      req.cookies().add(cookie);
+    } else if let Some(jar) = req.cookies().signed() {
+      jar.add(cookie);
+    }
+  }
+
+  /// Returns the token sent in the header, or the form field of a
+  /// url-encoded body, leaving the body in place for the handler.
+  async fn submitted_token(&self, req: &mut Request) -> Result<Option<String>, BodyError> {
+    if let Some(token) = req.headers().get(&self.header_name) {
+      return Ok(token.to_str().ok().map(str::to_owned));
+    }
+
+    let is_form = req.content_type().is_some_and(|mime| {
+      mime.essence_str() == mime::APPLICATION_WWW_FORM_URLENCODED.essence_str()
+    });
+
+    if !is_form {
+      return Ok(None);
+    }
+
+    let body = req.bytes().await?;
+    let token = form_urlencoded::parse(&body)
+      .find(|(name, _)| *name == self.field_name)
+      .map(|(_, value)| value.into_owned());
+
+    *req.body_mut() = Body::Once(body);
+    Ok(token)
+  }
+}
+
+#[crate::async_trait]
+impl Middleware for Csrf {
+  async fn call(&self, mut req: Request, next: Next<'_>) -> Response {
+    if !self.double_submit && req.cookies().signed().is_none() {
+      log::error!(
+        "CSRF protection requires `Config::secret_key` to be set, or `Csrf::double_submit`"
+      );
+      return error(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let secret = self.secret(&req);
+    let secret = match secret {
+      Some(secret) => secret,
+      None => {
+        let mut secret = vec![0; SECRET_LEN];
+        rand::thread_rng().fill_bytes(&mut secret);
+        self.set_secret(&req, &secret);
+
+        if !self.is_exempt(&req) {
+          return (self.rejection)(CsrfError::MissingCookie);
+        }
+
+        secret
+      }
+    };
+
+    if !self.is_exempt(&req) {
+      let token = match self.submitted_token(&mut req).await {
+        Ok(Some(token)) => token,
+        Ok(None) => return (self.rejection)(CsrfError::MissingToken),
+        // a body that is too large or too slow is not a forgery
+        Err(err) => return error(err.status()),
+      };
+
+      if !verify(&token, &secret) {
+        return (self.rejection)(CsrfError::InvalidToken);
+      }
+    }
+
+    req.extensions_mut().insert(CsrfToken(mask(&secret)));
+    next.run(req).await
+  }
+}
+
+fn error(status: StatusCode) -> Response {
+  Response::builder()
+    .status(status)
+    .body(Body::empty())
+    .unwrap()
+}
+
+/// Masks the secret with a random one-time pad, so the token differs
+/// between responses.
+fn mask(secret: &[u8]) -> String {
+  let mut pad = vec![0; secret.len()];
+  rand::thread_rng().fill_bytes(&mut pad);
+
+  let masked: Vec<u8> = pad.iter().zip(secret).map(|(p, s)| p ^ s).collect();
+  encode_hex(&pad) + &encode_hex(&masked)
+}
+
+/// Checks a masked token, or the raw secret as sent by scripts using the
+/// double-submit cookie.
+fn verify(token: &str, secret: &[u8]) -> bool {
+  let token = match decode_hex(token) {
+    Some(token) => token,
+    None => return false,
+  };
+
+  let unmasked: Vec<u8> = if token.len() == secret.len() * 2 {
+    let (pad, masked) = token.split_at(secret.len());
+    pad.iter().zip(masked).map(|(p, m)| p ^ m).collect()
+  } else {
+    token
+  };
+
+  unmasked.len() == secret.len() && bool::from(unmasked.ct_eq(secret))
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+  if !s.len().is_multiple_of(2) || !s.is_ascii() {
+    return None;
+  }
+
+  (0..s.len())
+    .step_by(2)
+    .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::Config;
+  use crate::http::{header, Key, Method};
+  use crate::router::{Route, Router};
+
+  fn router(csrf: Csrf) -> Router {
+    let mut router = Router::default();
+    let token = |req: Request| async move {
+      let token = req.extensions().get::<CsrfToken>().unwrap();
+      Response::new(token.as_str().to_owned())
+    };
+    router.route(Route::new(Method::GET, "/form", token));
+    router.route(Route::new(Method::POST, "/form", token));
+    router.route(Route::new(Method::POST, "/hooks/github", token));
+    router.route(Route::new(Method::POST, "/login", token).named("login"));
+    router.middleware(csrf);
+    router
+  }
+
+  async fn send(
+    router: &Router,
+    config: &Config,
+    req: hyper::http::request::Builder,
+    body: impl Into<hyper::Body>,
+  ) -> (StatusCode, Option<String>, String) {
+    let res = router
+      .serve(req.body(body.into()).unwrap().into(), config)
+      .await
+      .unwrap();
+
+    let cookie = res.headers().get(header::SET_COOKIE).map(|value| {
+      value
+        .to_str()
+        .unwrap()
+        .split(';')
+        .next()
+        .unwrap()
+        .to_owned()
+    });
+
+    let status = res.status();
+    let body = match res.into_body() {
+      Body::Once(bytes) => String::from_utf8(bytes.to_vec()).unwrap(),
+      _ => String::new(),
+    };
+
+    (status, cookie, body)
+  }
+
+  #[tokio::test]
+  async fn signed() {
+    let config = Config::default().secret_key(Key::generate());
+    let router = router(Csrf::new().exempt_prefix("/hooks/").exempt_route("login"));
+    let post = |uri| hyper::Request::builder().method(Method::POST).uri(uri);
+
+    let (status, cookie, token) =
+      send(&router, &config, hyper::Request::builder().uri("/form"), "").await;
+    assert_eq!(status, StatusCode::OK);
+    let cookie = cookie.unwrap();
+
+    let (status, _, _) = send(&router, &config, post("/form"), "").await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _, _) = send(
+      &router,
+      &config,
+      post("/form").header(header::COOKIE, &cookie),
+      "",
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let req = post("/form")
+      .header(header::COOKIE, &cookie)
+      .header("x-csrf-token", &token);
+    let (status, _, next) = send(&router, &config, req, "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_ne!(next, token);
+
+    let form = format!("name=ferris&_csrf={}", token);
+    let req = post("/form")
+      .header(header::COOKIE, &cookie)
+      .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded");
+    assert_eq!(
+      send(&router, &config, req, form.clone()).await.0,
+      StatusCode::OK
+    );
+
+    // media types are case-insensitive
+    let req = post("/form").header(header::COOKIE, &cookie).header(
+      header::CONTENT_TYPE,
+      "Application/X-WWW-Form-URLEncoded; charset=UTF-8",
+    );
+    assert_eq!(
+      send(&router, &config, req, form.clone()).await.0,
+      StatusCode::OK
+    );
+
+    // body errors are not reported as a missing token
+    let limited = config.clone().body_limit(8);
+    let req = post("/form")
+      .header(header::COOKIE, &cookie)
+      .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded");
+    assert_eq!(
+      send(&router, &limited, req, form).await.0,
+      StatusCode::PAYLOAD_TOO_LARGE
+    );
+
+    let req = post("/form")
+      .header(header::COOKIE, &cookie)
+      .header("x-csrf-token", "00");
+    assert_eq!(
+      send(&router, &config, req, "").await.0,
+      StatusCode::FORBIDDEN
+    );
+
+    assert_eq!(
+      send(&router, &config, post("/hooks/github"), "").await.0,
+      StatusCode::OK
+    );
+    assert_eq!(
+      send(&router, &config, post("/login"), "").await.0,
+      StatusCode::OK
+    );
+    assert_eq!(
+      send(&router, &config, post("/missing"), "").await.0,
+      StatusCode::NOT_FOUND
+    );
+  }
+
+  #[tokio::test]
+  async fn without_secret_key() {
+    let router = router(Csrf::new());
+    let req = hyper::Request::builder().uri("/form");
+    let (status, cookie, _) = send(&router, &Config::default(), req, "").await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(cookie.is_none());
+  }
+
+  #[tokio::test]
+  async fn header_name() {
+    let config = Config::default();
+    let router = router(
+      Csrf::new()
+        .double_submit()
+        .header_name(HeaderName::from_static("x-xsrf-token")),
+    );
+
+    let (_, cookie, _) = send(&router, &config, hyper::Request::builder().uri("/form"), "").await;
+    let cookie = cookie.unwrap();
+    let secret = cookie.trim_start_matches("csrf=");
+
+    let req = hyper::Request::builder()
+      .method(Method::POST)
+      .uri("/form")
+      .header(header::COOKIE, &cookie)
+      .header("X-XSRF-Token", secret);
+    assert_eq!(send(&router, &config, req, "").await.0, StatusCode::OK);
+  }
+
+  #[tokio::test]
+  async fn double_submit() {
+    let config = Config::default();
+    let router = router(Csrf::new().double_submit());
+
+    let (_, cookie, _) = send(&router, &config, hyper::Request::builder().uri("/form"), "").await;
+    let cookie = cookie.unwrap();
+    let secret = cookie.trim_start_matches("csrf=");
+
+    let req = hyper::Request::builder()
+      .method(Method::POST)
+      .uri("/form")
+      .header(header::COOKIE, &cookie)
+      .header("x-csrf-token", secret);
+    assert_eq!(send(&router, &config, req, "").await.0, StatusCode::OK);
+  }
+}
@@ -0,0 +1,296 @@
+use crate::http::{header, Method, Request, Response, StatusCode};
+use crate::middleware::{Middleware, Next};
+use crate::server::Connections;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Prometheus metrics middleware.
+///
+/// Records the number and latency of requests, labelled by method, matched
+/// route pattern and status, and serves them in the Prometheus text
+/// exposition format on `GET /metrics`:
+///
+/// ```text
+/// turbofish_http_requests_total{method="GET",route="/users/:id",status="200"} 2
+/// turbofish_http_request_duration_seconds_bucket{method="GET",route="/users/:id",status="200",le="0.005"} 2
+/// turbofish_http_requests_in_flight 1
+/// turbofish_connections_open 1
+/// ```
+///
+/// Requests that don't match a route are labelled with `route="unmatched"`
+/// and non-standard methods with `method="other"`, so that the number of
+/// series doesn't grow with what clients send. Requests to the metrics endpoint itself aren't recorded, and
+/// access to it can be restricted with middleware added before this one.
+pub struct Metrics {
+  path: String,
+  buckets: Vec<f64>,
+  in_flight: AtomicI64,
+  series: Mutex<BTreeMap<Labels, Series>>,
+}
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Labels {
+  route: String,
+  method: &'static str,
+  status: u16,
+}
+
+struct Series {
+  count: u64,
+  sum: f64,
+  buckets: Vec<u64>,
+}
+
+impl Default for Metrics {
+  fn default() -> Self {
+    Self {
+      path: "/metrics".to_owned(),
+      buckets: vec![
+        0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+      ],
+      in_flight: AtomicI64::new(0),
+      series: Mutex::new(BTreeMap::new()),
+    }
+  }
+}
+
+impl Metrics {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Sets the path metrics are served on (default is `/metrics`).
+  pub fn path(mut self, path: impl Into<String>) -> Self {
+    self.path = path.into();
+    self
+  }
+
+  /// Sets the upper bounds of the latency histogram buckets, in seconds
+  /// (default is the Prometheus client default, from 5ms to 10s).
+  pub fn buckets(mut self, buckets: impl IntoIterator<Item = f64>) -> Self {
+    self.buckets = buckets.into_iter().collect();
+    self.buckets.sort_by(f64::total_cmp);
+    self
+  }
+
+  fn record(&self, labels: Labels, seconds: f64) {
+    let mut series = self.series.lock().unwrap();
+    let series = series.entry(labels).or_insert_with(|| Series {
+      count: 0,
+      sum: 0.0,
+      buckets: vec![0; self.buckets.len()],
+    });
+
+    series.count += 1;
+    series.sum += seconds;
+    for (count, bound) in series.buckets.iter_mut().zip(&self.buckets) {
+      if seconds <= *bound {
+        *count += 1;
+      }
+    }
+  }
+
+  /// Renders all metrics in the text exposition format.
+  fn render(&self, connections: Option<&Connections>) -> String {
+    let series = self.series.lock().unwrap();
+    let mut out = String::new();
+
+    out.push_str("# HELP turbofish_http_requests_total Total number of HTTP requests.\n");
+    out.push_str("# TYPE turbofish_http_requests_total counter\n");
+    for (labels, series) in series.iter() {
+      let _ = writeln!(
+        out,
+        "turbofish_http_requests_total{{{}}} {}",
+        labels, series.count
+      );
+    }
+
+    out.push_str("# HELP turbofish_http_request_duration_seconds HTTP request latency.\n");
+    out.push_str("# TYPE turbofish_http_request_duration_seconds histogram\n");
+    for (labels, series) in series.iter() {
+      let name = "turbofish_http_request_duration_seconds";
+      for (count, bound) in series.buckets.iter().zip(&self.buckets) {
+        let _ = writeln!(
+          out,
+          "{}_bucket{{{},le=\"{}\"}} {}",
+          name, labels, bound, count
+        );
+      }
+      let _ = writeln!(
+        out,
+        "{}_bucket{{{},le=\"+Inf\"}} {}",
+        name, labels, series.count
+      );
+      let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, series.sum);
+      let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, series.count);
+    }
+
+    out
+      .push_str("# HELP turbofish_http_requests_in_flight Number of HTTP requests being served.\n");
+    out.push_str("# TYPE turbofish_http_requests_in_flight gauge\n");
+    let _ = writeln!(
+      out,
+      "turbofish_http_requests_in_flight {}",
+      self.in_flight.load(Ordering::Relaxed)
+    );
+
+    if let Some(connections) = connections {
+      out.push_str("# HELP turbofish_connections_open Number of open connections.\n");
+      out.push_str("# TYPE turbofish_connections_open gauge\n");
+      let _ = writeln!(out, "turbofish_connections_open {}", connections.open());
+      out.push_str("# HELP turbofish_connections_total Total number of accepted connections.\n");
+      out.push_str("# TYPE turbofish_connections_total counter\n");
+      let _ = writeln!(out, "turbofish_connections_total {}", connections.total());
+    }
+
+    out
+  }
+}
+
+#[crate::async_trait]
+impl Middleware for Metrics {
+  async fn call(&self, req: Request, next: Next<'_>) -> Response {
+    if req.path() == self.path && *req.method() == Method::GET {
+      let body = self.render(req.extensions().get::<Connections>());
+      return Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/plain; version=0.0.4")
+        .body(body)
+        .unwrap();
+    }
+
+    let route = match req.route() {
+      Some(route) => route.path().to_owned(),
+      None => "unmatched".to_owned(),
+    };
+    let method = method_label(req.method());
+
+    let start = Instant::now();
+    let in_flight = InFlight::new(&self.in_flight);
+    let res = next.run(req).await;
+    drop(in_flight);
+
+    let labels = Labels {
+      route,
+      method,
+      status: res.status().as_u16(),
+    };
+    self.record(labels, start.elapsed().as_secs_f64());
+
+    res
+  }
+}
+
+/// Decrements the in-flight gauge even if the request is cancelled.
+struct InFlight<'a>(&'a AtomicI64);
+
+impl<'a> InFlight<'a> {
+  fn new(gauge: &'a AtomicI64) -> Self {
+    gauge.fetch_add(1, Ordering::Relaxed);
+    Self(gauge)
+  }
+}
+
+impl Drop for InFlight<'_> {
+  fn drop(&mut self) {
+    self.0.fetch_sub(1, Ordering::Relaxed);
+  }
+}
+
+impl std::fmt::Display for Labels {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "method=\"{}\",route=\"{}\",status=\"{}\"",
+      self.method,
+      escape(&self.route),
+      self.status
+    )
+  }
+}
+
+fn method_label(method: &Method) -> &'static str {
+  match *method {
+    Method::GET => "GET",
+    Method::HEAD => "HEAD",
+    Method::POST => "POST",
+    Method::PUT => "PUT",
+    Method::DELETE => "DELETE",
+    Method::CONNECT => "CONNECT",
+    Method::OPTIONS => "OPTIONS",
+    Method::TRACE => "TRACE",
+    Method::PATCH => "PATCH",
+    _ => "other",
+  }
+}
+
+fn escape(value: &str) -> String {
+  value
+    .replace('\\', "\\\\")
+    .replace('"', "\\\"")
+    .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::Config;
+  use crate::http::Body;
+  use crate::router::{Route, Router};
+
+  #[tokio::test]
+  async fn metrics() {
+    let mut router = Router::default();
+    router.route(Route::new(Method::GET, "/users/:id", |_: Request| async {
+      Response::new("")
+    }));
+    router.middleware(Metrics::new());
+
+    let config = Config::default();
+    let send = |path: &str| {
+      let req = hyper::Request::get(path)
+        .body(hyper::Body::empty())
+        .unwrap();
+      router.serve(req.into(), &config)
+    };
+
+    send("/users/1").await.unwrap();
+    send("/users/2").await.unwrap();
+    send("/unknown/1").await.unwrap();
+
+    let req = hyper::Request::builder().method("PURGE").uri("/users/1");
+    router
+      .serve(req.body(hyper::Body::empty()).unwrap().into(), &config)
+      .await
+      .unwrap();
+
+    let res = send("/metrics").await.unwrap();
+    assert_eq!(
+      res.headers()[header::CONTENT_TYPE],
+      "text/plain; version=0.0.4"
+    );
+    let body = match res.body() {
+      Body::Once(bytes) => String::from_utf8(bytes.to_vec()).unwrap(),
+      _ => unreachable!(),
+    };
+
+    assert!(body.contains(
+      "turbofish_http_requests_total{method=\"GET\",route=\"/users/:id\",status=\"200\"} 2\n"
+    ));
+    assert!(body.contains(
+      "turbofish_http_requests_total{method=\"GET\",route=\"unmatched\",status=\"404\"} 1\n"
+    ));
+    assert!(body.contains(
+      "turbofish_http_request_duration_seconds_bucket{method=\"GET\",route=\"/users/:id\",status=\"200\",le=\"+Inf\"} 2\n"
+    ));
+    assert!(body.contains(
+      "turbofish_http_requests_total{method=\"other\",route=\"unmatched\",status=\"405\"} 1\n"
+    ));
+    assert!(!body.contains("PURGE"));
+    assert!(body.contains("turbofish_http_requests_in_flight 0\n"));
+    assert!(!body.contains("/metrics"));
+  }
+}
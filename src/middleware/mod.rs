@@ -0,0 +1,77 @@
+pub mod auth;
+pub mod catch_panic;
+pub mod conditional;
+pub mod cors;
+pub mod csrf;
+pub mod metrics;
+pub mod rate_limit;
+pub mod security_headers;
+pub mod session;
+
+#[doc(inline)]
+pub use auth::Authenticate;
+
+#[doc(inline)]
+pub use catch_panic::CatchPanic;
+
+#[doc(inline)]
+pub use conditional::Conditional;
+
+#[doc(inline)]
+pub use cors::Cors;
+
+#[doc(inline)]
+pub use csrf::Csrf;
+
+#[doc(inline)]
+pub use metrics::Metrics;
+
+#[doc(inline)]
+pub use rate_limit::RateLimit;
+
+#[doc(inline)]
+pub use security_headers::SecurityHeaders;
+
+#[doc(inline)]
+pub use session::Sessions;
+
+use crate::http::{Request, Response};
+use crate::router::Router;
+
+/// Middleware wraps every request served by a [`Router`](crate::router::Router).
+///
+/// A middleware can inspect or modify the request, answer it directly, or
+/// pass it on to the rest of the stack by calling [`Next::run`] and then
+/// modify the response.
+#[crate::async_trait]
+pub trait Middleware: Send + Sync {
+  async fn call(&self, req: Request, next: Next<'_>) -> Response;
+}
+
+/// The remainder of the middleware stack, ending with the matched route.
+pub struct Next<'a> {
+  router: &'a Router,
+  middleware: &'a [Box<dyn Middleware>],
+}
+
+impl<'a> Next<'a> {
+  pub(crate) fn new(router: &'a Router, middleware: &'a [Box<dyn Middleware>]) -> Self {
+    Self { router, middleware }
+  }
+
+  /// Returns the router serving the request.
+  pub fn router(&self) -> &'a Router {
+    self.router
+  }
+
+  /// Runs the request through the rest of the stack.
+  pub async fn run(mut self, req: Request) -> Response {
+    match self.middleware.split_first() {
+      Some((current, rest)) => {
+        self.middleware = rest;
+        current.call(req, self).await
+      }
+      None => self.router.dispatch(req).await,
+    }
+  }
+}
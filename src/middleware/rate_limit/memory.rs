@@ -0,0 +1,284 @@
+use super::{Algorithm, Decision, Quota, Store};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::error::Error;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// An in-process [`Store`], with counters split across a number of
+/// independently locked shards to reduce contention.
+pub struct MemoryStore {
+  shards: Vec<Mutex<Shard>>,
+}
+
+struct Shard {
+  counters: HashMap<String, Counter>,
+  last_sweep: Instant,
+}
+
+struct Counter {
+  // the period of the quota the counter was last hit with
+  period: Duration,
+  state: State,
+}
+
+enum State {
+  Bucket {
+    tokens: f64,
+    updated: Instant,
+  },
+  Window {
+    start: Instant,
+    previous: u64,
+    current: u64,
+  },
+}
+
+// how often a shard drops counters that have fully reset
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+impl Default for MemoryStore {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl MemoryStore {
+  pub fn new() -> Self {
+    Self::with_shards(16)
+  }
+
+  /// Creates a store with the given number of shards.
+  pub fn with_shards(shards: usize) -> Self {
+    assert!(shards > 0, "memory store must have at least one shard");
+
+    Self {
+      shards: (0..shards)
+        .map(|_| {
+          Mutex::new(Shard {
+            counters: HashMap::new(),
+            last_sweep: Instant::now(),
+          })
+        })
+        .collect(),
+    }
+  }
+
+  fn shard(&self, key: &str) -> &Mutex<Shard> {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    &self.shards[hasher.finish() as usize % self.shards.len()]
+  }
+
+  /// Counts a request at `now`.
+  fn hit_at(&self, key: &str, quota: &Quota, now: Instant) -> Decision {
+    let mut shard = self.shard(key).lock().unwrap();
+
+    if now.duration_since(shard.last_sweep) > SWEEP_INTERVAL {
+      shard.counters.retain(|_, counter| !counter.is_reset(now));
+      shard.last_sweep = now;
+    }
+
+    let counter = shard
+      .counters
+      .entry(key.to_owned())
+      .or_insert_with(|| Counter::new(quota, now));
+
+    counter.hit(quota, now)
+  }
+}
+
+#[crate::async_trait]
+impl Store for MemoryStore {
+  async fn hit(&self, key: &str, quota: &Quota) -> Result<Decision, Box<dyn Error + Send + Sync>> {
+    Ok(self.hit_at(key, quota, Instant::now()))
+  }
+}
+
+impl Counter {
+  fn new(quota: &Quota, now: Instant) -> Self {
+    let state = match quota.algorithm {
+      Algorithm::TokenBucket => State::Bucket {
+        tokens: quota.limit as f64,
+        updated: now,
+      },
+      Algorithm::SlidingWindow => State::Window {
+        start: now,
+        previous: 0,
+        current: 0,
+      },
+    };
+
+    Self {
+      period: quota.period,
+      state,
+    }
+  }
+
+  /// Whether the counter is back to its initial state. Counters in a shard
+  /// may belong to different quotas, so each is checked against the period
+  /// of its own quota rather than that of the current request.
+  fn is_reset(&self, now: Instant) -> bool {
+    match self.state {
+      State::Bucket { updated, .. } => now.duration_since(updated) >= self.period,
+      State::Window { start, .. } => now.duration_since(start) >= self.period * 2,
+    }
+  }
+
+  fn hit(&mut self, quota: &Quota, now: Instant) -> Decision {
+    let limit = quota.limit as f64;
+    let period = quota.period.as_secs_f64();
+    self.period = quota.period;
+
+    match &mut self.state {
+      State::Bucket { tokens, updated } => {
+        // refill at `limit` tokens per period
+        let rate = limit / period;
+        *tokens = (*tokens + now.duration_since(*updated).as_secs_f64() * rate).min(limit);
+        *updated = now;
+
+        let allowed = *tokens >= 1.0;
+        if allowed {
+          *tokens -= 1.0;
+        }
+
+        let reset = if allowed {
+          (limit - *tokens) / rate
+        } else {
+          (1.0 - *tokens) / rate
+        };
+
+        Decision {
+          allowed,
+          limit: quota.limit,
+          remaining: tokens.floor() as u64,
+          reset: Duration::from_secs_f64(reset),
+        }
+      }
+      State::Window {
+        start,
+        previous,
+        current,
+      } => {
+        let elapsed = now.duration_since(*start);
+        if elapsed >= quota.period * 2 {
+          *previous = 0;
+          *current = 0;
+          *start =
+            now - Duration::from_nanos((elapsed.as_nanos() % quota.period.as_nanos()) as u64);
+        } else if elapsed >= quota.period {
+          *previous = *current;
+          *current = 0;
+          *start += quota.period;
+        }
+
+        // weight the previous window by how much of it overlaps the
+        // sliding window ending now
+        let into_window = now.duration_since(*start).as_secs_f64();
+        let weight = 1.0 - into_window / period;
+        let estimate = *previous as f64 * weight + *current as f64;
+
+        let allowed = estimate + 1.0 <= limit;
+        if allowed {
+          *current += 1;
+        }
+
+        let used = (estimate + f64::from(u8::from(allowed))).ceil() as u64;
+
+        Decision {
+          allowed,
+          limit: quota.limit,
+          remaining: quota.limit.saturating_sub(used),
+          reset: Duration::from_secs_f64(period - into_window),
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn token_bucket() {
+    let store = MemoryStore::with_shards(1);
+    let quota = Quota::new(2, Duration::from_secs(10));
+    let now = Instant::now();
+
+    assert!(store.hit_at("a", &quota, now).allowed);
+    assert!(store.hit_at("a", &quota, now).allowed);
+
+    let decision = store.hit_at("a", &quota, now);
+    assert!(!decision.allowed);
+    assert_eq!(decision.reset, Duration::from_secs(5));
+
+    // one token refills every 5 seconds
+    let decision = store.hit_at("a", &quota, now + Duration::from_secs(5));
+    assert!(decision.allowed);
+    assert_eq!(decision.remaining, 0);
+    assert!(
+      !store
+        .hit_at("a", &quota, now + Duration::from_secs(6))
+        .allowed
+    );
+    assert!(store.hit_at("b", &quota, now).allowed);
+  }
+
+  #[test]
+  fn sliding_window() {
+    let store = MemoryStore::with_shards(1);
+    let quota = Quota::new(4, Duration::from_secs(10)).algorithm(Algorithm::SlidingWindow);
+    let now = Instant::now();
+
+    for remaining in (0..4).rev() {
+      let decision = store.hit_at("a", &quota, now);
+      assert!(decision.allowed);
+      assert_eq!(decision.remaining, remaining);
+    }
+
+    assert!(
+      !store
+        .hit_at("a", &quota, now + Duration::from_secs(9))
+        .allowed
+    );
+
+    // halfway through the next window, half of the previous window counts
+    let later = now + Duration::from_secs(15);
+    assert!(store.hit_at("a", &quota, later).allowed);
+    assert!(store.hit_at("a", &quota, later).allowed);
+    assert!(!store.hit_at("a", &quota, later).allowed);
+
+    // both windows have passed
+    assert!(
+      store
+        .hit_at("a", &quota, now + Duration::from_secs(30))
+        .allowed
+    );
+  }
+
+  #[test]
+  fn sweep_uses_counter_period() {
+    let store = MemoryStore::with_shards(1);
+    let short = Quota::per_second(1);
+    let long = Quota::per_hour(1);
+    let now = Instant::now();
+
+    assert!(store.hit_at("long", &long, now).allowed);
+    assert!(!store.hit_at("long", &long, now).allowed);
+
+    // sweeping the shard for a request with the short quota must not drop
+    // the counter of the long one
+    let later = now + SWEEP_INTERVAL + Duration::from_secs(1);
+    assert!(store.hit_at("short", &short, later).allowed);
+    assert!(!store.hit_at("long", &long, later).allowed);
+
+    // counters that did reset are dropped
+    let sweep = later + SWEEP_INTERVAL + Duration::from_secs(1);
+    store.hit_at("long", &long, sweep);
+    let shard = store.shards[0].lock().unwrap();
+    assert!(!shard.counters.contains_key("short"));
+    assert!(shard.counters.contains_key("long"));
+  }
+}
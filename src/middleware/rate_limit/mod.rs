@@ -0,0 +1,392 @@
+mod memory;
+
+pub use memory::MemoryStore;
+
+use crate::http::{
+  header, Body, HeaderMap, HeaderName, HeaderValue, Request, Response, StatusCode,
+};
+use crate::middleware::{Middleware, Next};
+use crate::router::Route;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The number of requests a client may make in a period of time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quota {
+  limit: u64,
+  period: Duration,
+  algorithm: Algorithm,
+}
+
+/// The algorithm used to enforce a [`Quota`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+  /// Allows bursts of up to `limit` requests, refilling at a constant rate
+  /// of `limit` requests per period.
+  TokenBucket,
+  /// Allows `limit` requests in any window of length `period`, estimated
+  /// from the counts of the current and previous fixed windows.
+  SlidingWindow,
+}
+
+impl Quota {
+  /// Returns a token bucket quota of `limit` requests per `period`.
+  pub fn new(limit: u64, period: Duration) -> Self {
+    assert!(limit > 0, "rate limit quota must be greater than zero");
+    assert!(
+      period > Duration::from_secs(0),
+      "rate limit period must be greater than zero"
+    );
+
+    Self {
+      limit,
+      period,
+      algorithm: Algorithm::TokenBucket,
+    }
+  }
+
+  pub fn per_second(limit: u64) -> Self {
+    Self::new(limit, Duration::from_secs(1))
+  }
+
+  pub fn per_minute(limit: u64) -> Self {
+    Self::new(limit, Duration::from_secs(60))
+  }
+
+  pub fn per_hour(limit: u64) -> Self {
+    Self::new(limit, Duration::from_secs(60 * 60))
+  }
+
+  /// Sets the algorithm used to enforce the quota (default is a token bucket).
+  pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+    self.algorithm = algorithm;
+    self
+  }
+
+  pub fn limit(&self) -> u64 {
+    self.limit
+  }
+
+  pub fn period(&self) -> Duration {
+    self.period
+  }
+}
+
+/// The outcome of counting a request against a [`Quota`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Decision {
+  /// Whether the request is allowed.
+  pub allowed: bool,
+  /// The quota limit.
+  pub limit: u64,
+  /// The number of requests remaining.
+  pub remaining: u64,
+  /// The time until the quota resets if the request is allowed, or until a
+  /// request will be allowed again if it is not.
+  pub reset: Duration,
+}
+
+/// Storage for rate limit counters.
+///
+/// The default [`MemoryStore`] keeps counters in process. Implement this
+/// trait to share counters between processes, for example in Redis.
+#[crate::async_trait]
+pub trait Store: Send + Sync {
+  /// Counts a request identified by `key` against the quota.
+  async fn hit(&self, key: &str, quota: &Quota) -> Result<Decision, Box<dyn Error + Send + Sync>>;
+}
+
+enum KeyBy {
+  ClientIp,
+  Header(HeaderName),
+  Fn(KeyFn),
+}
+
+type KeyFn = Box<dyn Fn(&Request) -> Option<String> + Send + Sync>;
+
+/// How requests without a key are limited, ex: requests without the header
+/// set with [`RateLimit::key_by_header`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MissingKey {
+  /// Key the request by client IP address instead (the default).
+  ClientIp,
+  /// Reject the request with the given status, ex: `401 Unauthorized`.
+  Reject(StatusCode),
+}
+
+/// Rate limiting middleware.
+///
+/// Requests are keyed by client IP address by default, and counted against
+/// the quota of the matched route if one was set with
+/// [`Route::rate_limit`], or the default quota otherwise. Responses carry
+/// the `RateLimit-Limit`, `RateLimit-Remaining` and `RateLimit-Reset`
+/// headers, and rejected requests are answered with `429 Too Many Requests`
+/// and a `Retry-After` header.
+///
+/// ```rust
+/// use turbofish::http::HeaderName;
+/// use turbofish::middleware::rate_limit::{Quota, RateLimit};
+///
+/// let limit = RateLimit::new(Quota::per_minute(60))
+///   .key_by_header(HeaderName::from_static("x-api-key"));
+/// ```
+pub struct RateLimit {
+  quota: Option<Quota>,
+  key: KeyBy,
+  missing_key: MissingKey,
+  store: Arc<dyn Store>,
+}
+
+impl RateLimit {
+  /// Creates a rate limiter with a default quota applied to every route.
+  pub fn new(quota: Quota) -> Self {
+    Self {
+      quota: Some(quota),
+      ..Self::per_route()
+    }
+  }
+
+  /// Creates a rate limiter that only limits routes with a quota set with
+  /// [`Route::rate_limit`].
+  pub fn per_route() -> Self {
+    Self {
+      quota: None,
+      key: KeyBy::ClientIp,
+      missing_key: MissingKey::ClientIp,
+      store: Arc::new(MemoryStore::new()),
+    }
+  }
+
+  /// Keys requests by the value of the given header, ex: an API key.
+  /// Requests without the header are handled as set with
+  /// [`on_missing_key`](RateLimit::on_missing_key).
+  pub fn key_by_header(mut self, name: HeaderName) -> Self {
+    self.key = KeyBy::Header(name);
+    self
+  }
+
+  /// Keys requests by the result of the closure. Requests for which the
+  /// closure returns `None` are handled as set with
+  /// [`on_missing_key`](RateLimit::on_missing_key).
+  pub fn key_by(mut self, f: impl Fn(&Request) -> Option<String> + Send + Sync + 'static) -> Self {
+    self.key = KeyBy::Fn(Box::new(f));
+    self
+  }
+
+  /// Sets how requests without a key are limited (default is
+  /// [`MissingKey::ClientIp`]).
+  pub fn on_missing_key(mut self, missing_key: MissingKey) -> Self {
+    self.missing_key = missing_key;
+    self
+  }
+
+  /// Sets the store used for counters (default is a [`MemoryStore`]).
+  pub fn store(mut self, store: impl Store + 'static) -> Self {
+    self.store = Arc::new(store);
+    self
+  }
+
+  /// Returns the key of the request, or the status to reject it with.
+  fn key(&self, req: &Request) -> Result<String, StatusCode> {
+    let key = match &self.key {
+      KeyBy::ClientIp => return Ok(client_ip(req)),
+      KeyBy::Header(name) => req
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned),
+      KeyBy::Fn(f) => f(req),
+    };
+
+    // prefixed so that a client can't pick a key that collides with the
+    // counter of another client's IP address
+    match (key, self.missing_key) {
+      (Some(key), _) => Ok(format!("key:{}", key)),
+      (None, MissingKey::ClientIp) => Ok(client_ip(req)),
+      (None, MissingKey::Reject(status)) => Err(status),
+    }
+  }
+}
+
+fn client_ip(req: &Request) -> String {
+  // requests without an address, ex: in tests, share a counter
+  match req.remote_addr() {
+    Some(addr) => format!("ip:{}", addr.ip()),
+    None => "ip:unknown".to_owned(),
+  }
+}
+
+#[crate::async_trait]
+impl Middleware for RateLimit {
+  async fn call(&self, req: Request, next: Next<'_>) -> Response {
+    let route_quota = req
+      .extensions()
+      .get::<Arc<Route>>()
+      .and_then(|route| Some((*route.extensions().get::<Quota>()?, route.path())));
+
+    let (quota, key) = match (route_quota, self.quota) {
+      // routes with their own quota get their own counters
+      (Some((quota, path)), _) => (quota, self.key(&req).map(|key| format!("{} {}", path, key))),
+      (None, Some(quota)) => (quota, self.key(&req)),
+      (None, None) => return next.run(req).await,
+    };
+
+    let key = match key {
+      Ok(key) => key,
+      Err(status) => {
+        return Response::builder()
+          .status(status)
+          .body(Body::empty())
+          .unwrap()
+      }
+    };
+
+    // fail open if the store is unavailable
+    let decision = match self.store.hit(&key, &quota).await {
+      Ok(decision) => decision,
+      Err(err) => {
+        log::warn!("rate limit store failed, allowing the request: {}", err);
+        return next.run(req).await;
+      }
+    };
+
+    let mut res = if decision.allowed {
+      next.run(req).await
+    } else {
+      let mut res = Response::builder()
+        .status(StatusCode::TOO_MANY_REQUESTS)
+        .body(Body::empty())
+        .unwrap();
+      res
+        .headers_mut()
+        .insert(header::RETRY_AFTER, seconds(decision.reset));
+      res
+    };
+
+    set_headers(res.headers_mut(), &decision);
+    res
+  }
+}
+
+fn set_headers(headers: &mut HeaderMap, decision: &Decision) {
+  headers.insert(
+    HeaderName::from_static("ratelimit-limit"),
+    decision.limit.into(),
+  );
+  headers.insert(
+    HeaderName::from_static("ratelimit-remaining"),
+    decision.remaining.into(),
+  );
+  headers.insert(
+    HeaderName::from_static("ratelimit-reset"),
+    seconds(decision.reset),
+  );
+}
+
+// rounds up, so clients never retry too early
+fn seconds(duration: Duration) -> HeaderValue {
+  let secs = duration.as_secs() + u64::from(duration.subsec_nanos() > 0);
+  secs.into()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::Config;
+  use crate::http::Method;
+  use crate::router::Router;
+
+  fn request(uri: &str, key: &str) -> Request {
+    hyper::Request::builder()
+      .uri(uri)
+      .header("x-api-key", key)
+      .body(hyper::Body::empty())
+      .unwrap()
+      .into()
+  }
+
+  #[tokio::test]
+  async fn rate_limit() {
+    let mut router = Router::default();
+    let ok = |_: Request| async { Response::new("ok") };
+    router.route(Route::new(Method::GET, "/", ok));
+    router.route(Route::new(Method::GET, "/login", ok).rate_limit(Quota::per_hour(1)));
+    router.middleware(
+      RateLimit::new(Quota::per_hour(2)).key_by_header(HeaderName::from_static("x-api-key")),
+    );
+
+    let config = Config::default();
+    let res = router.serve(request("/", "a"), &config).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["ratelimit-limit"], "2");
+    assert_eq!(res.headers()["ratelimit-remaining"], "1");
+
+    let res = router.serve(request("/", "a"), &config).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["ratelimit-remaining"], "0");
+
+    let res = router.serve(request("/", "a"), &config).await.unwrap();
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(res.headers()["retry-after"], "1800");
+
+    // separate keys and routes have separate counters
+    let res = router.serve(request("/", "b"), &config).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = router.serve(request("/login", "a"), &config).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["ratelimit-limit"], "1");
+
+    let res = router.serve(request("/login", "a"), &config).await.unwrap();
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+  }
+
+  #[tokio::test]
+  async fn missing_key() {
+    let key = HeaderName::from_static("x-api-key");
+    let ok = |_: Request| async { Response::new("ok") };
+    let from = |ip: &str| {
+      let mut req: Request = hyper::Request::builder()
+        .uri("/")
+        .body(hyper::Body::empty())
+        .unwrap()
+        .into();
+      req.set_remote_addr(format!("{}:443", ip).parse().unwrap());
+      req
+    };
+
+    let mut router = Router::default();
+    router.route(Route::new(Method::GET, "/", ok));
+    router.middleware(RateLimit::new(Quota::per_hour(1)).key_by_header(key.clone()));
+
+    // requests without the header are limited by IP
+    let config = Config::default();
+    let res = router.serve(from("10.0.0.1"), &config).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = router.serve(from("10.0.0.1"), &config).await.unwrap();
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    let res = router.serve(from("10.0.0.2"), &config).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // a key can't be picked to exhaust the counter of an IP
+    let res = router
+      .serve(request("/", "10.0.0.3"), &config)
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = router.serve(from("10.0.0.3"), &config).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let mut router = Router::default();
+    router.route(Route::new(Method::GET, "/", ok));
+    router.middleware(
+      RateLimit::new(Quota::per_hour(1))
+        .key_by(|_| None)
+        .on_missing_key(MissingKey::Reject(StatusCode::UNAUTHORIZED)),
+    );
+
+    let res = router.serve(from("10.0.0.1"), &config).await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+  }
+}
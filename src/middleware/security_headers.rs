@@ -0,0 +1,642 @@
+use crate::http::{header, HeaderMap, HeaderName, HeaderValue, Request, Response};
+use crate::middleware::{Middleware, Next};
+use rand::RngCore;
+use std::time::Duration;
+
+/// The `Strict-Transport-Security` policy, telling browsers to only
+/// connect over HTTPS.
+#[derive(Clone, Debug)]
+pub struct Hsts {
+  max_age: Duration,
+  include_subdomains: bool,
+  preload: bool,
+}
+
+impl Hsts {
+  /// Creates a policy remembered for `max_age`.
+  pub fn new(max_age: Duration) -> Self {
+    Self {
+      max_age,
+      include_subdomains: false,
+      preload: false,
+    }
+  }
+
+  /// Applies the policy to every subdomain.
+  pub fn include_subdomains(mut self) -> Self {
+    self.include_subdomains = true;
+    self
+  }
+
+  /// Allows the domain to be added to the browser preload lists.
+  pub fn preload(mut self) -> Self {
+    self.preload = true;
+    self
+  }
+
+  fn to_header(&self) -> HeaderValue {
+    let mut value = format!("max-age={}", self.max_age.as_secs());
+    if self.include_subdomains {
+      value.push_str("; includeSubDomains");
+    }
+    if self.preload {
+      value.push_str("; preload");
+    }
+    HeaderValue::from_str(&value).expect("HSTS header is a valid header value")
+  }
+}
+
+impl Default for Hsts {
+  /// One year, including subdomains.
+  fn default() -> Self {
+    Self::new(Duration::from_secs(365 * 24 * 60 * 60)).include_subdomains()
+  }
+}
+
+/// The `X-Frame-Options` header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameOptions {
+  Deny,
+  SameOrigin,
+}
+
+/// The `Referrer-Policy` header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReferrerPolicy {
+  NoReferrer,
+  NoReferrerWhenDowngrade,
+  Origin,
+  OriginWhenCrossOrigin,
+  SameOrigin,
+  StrictOrigin,
+  StrictOriginWhenCrossOrigin,
+  UnsafeUrl,
+}
+
+impl ReferrerPolicy {
+  fn as_str(&self) -> &'static str {
+    match self {
+      ReferrerPolicy::NoReferrer => "no-referrer",
+      ReferrerPolicy::NoReferrerWhenDowngrade => "no-referrer-when-downgrade",
+      ReferrerPolicy::Origin => "origin",
+      ReferrerPolicy::OriginWhenCrossOrigin => "origin-when-cross-origin",
+      ReferrerPolicy::SameOrigin => "same-origin",
+      ReferrerPolicy::StrictOrigin => "strict-origin",
+      ReferrerPolicy::StrictOriginWhenCrossOrigin => "strict-origin-when-cross-origin",
+      ReferrerPolicy::UnsafeUrl => "unsafe-url",
+    }
+  }
+}
+
+/// The `Cross-Origin-Opener-Policy` header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpenerPolicy {
+  SameOrigin,
+  SameOriginAllowPopups,
+  UnsafeNone,
+}
+
+/// The `Cross-Origin-Embedder-Policy` header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmbedderPolicy {
+  RequireCorp,
+  Credentialless,
+  UnsafeNone,
+}
+
+/// A source allowed by a [`ContentSecurityPolicy`] directive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Source {
+  /// `'none'`
+  None,
+  /// `'self'`
+  SelfOrigin,
+  /// `'unsafe-inline'`
+  UnsafeInline,
+  /// `'unsafe-eval'`
+  UnsafeEval,
+  /// `'strict-dynamic'`
+  StrictDynamic,
+  /// The [`CspNonce`] of the current request.
+  Nonce,
+  /// A host, ex: `https://cdn.example.com` or `*.example.com`.
+  Host(String),
+  /// A scheme, ex: `data:` or `https:`.
+  Scheme(String),
+}
+
+impl Source {
+  fn write(&self, nonce: Option<&str>, out: &mut String) {
+    match self {
+      Source::None => out.push_str("'none'"),
+      Source::SelfOrigin => out.push_str("'self'"),
+      Source::UnsafeInline => out.push_str("'unsafe-inline'"),
+      Source::UnsafeEval => out.push_str("'unsafe-eval'"),
+      Source::StrictDynamic => out.push_str("'strict-dynamic'"),
+      Source::Nonce => {
+        out.push_str("'nonce-");
+        out.push_str(nonce.unwrap_or_default());
+        out.push('\'');
+      }
+      Source::Host(host) => out.push_str(host),
+      Source::Scheme(scheme) => out.push_str(scheme),
+    }
+  }
+
+  fn validate(&self) {
+    match self {
+      Source::Host(value) | Source::Scheme(value) => assert!(
+        is_token(value),
+        "invalid CSP source `{}`: sources cannot be empty or contain whitespace, quotes, `;` or `,`",
+        value.escape_debug()
+      ),
+      _ => {}
+    }
+  }
+}
+
+/// Whether `value` can be used as a CSP source or report uri without
+/// changing the meaning of the rest of the policy.
+fn is_token(value: &str) -> bool {
+  !value.is_empty()
+    && value
+      .bytes()
+      .all(|b| b.is_ascii_graphic() && !matches!(b, b';' | b',' | b'\'' | b'"'))
+}
+
+/// The nonce of the current request, for use in the `nonce` attribute of
+/// inline scripts and styles allowed with [`Source::Nonce`].
+///
+/// ```rust
+/// use turbofish::http::{Request, Response};
+/// use turbofish::middleware::security_headers::CspNonce;
+///
+/// async fn page(req: Request) -> Response {
+///   let nonce = req.extensions().get::<CspNonce>().unwrap();
+///   Response::new(format!(r#"<script nonce="{}">run()</script>"#, nonce.as_str()))
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct CspNonce(String);
+
+impl CspNonce {
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+}
+
+/// A `Content-Security-Policy`.
+///
+/// Hosts, schemes and the report uri are checked as they are added, and
+/// the builder panics on values that could inject other directives, such
+/// as `example.com; script-src *`.
+///
+/// ```rust
+/// use turbofish::middleware::security_headers::{ContentSecurityPolicy, Source};
+///
+/// let csp = ContentSecurityPolicy::new()
+///   .default_src(vec![Source::SelfOrigin])
+///   .script_src(vec![Source::SelfOrigin, Source::Nonce])
+///   .img_src(vec![Source::SelfOrigin, Source::Scheme("data:".into())])
+///   .object_src(vec![Source::None]);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ContentSecurityPolicy {
+  directives: Vec<(&'static str, Vec<Source>)>,
+  upgrade_insecure_requests: bool,
+  report_uri: Option<String>,
+  report_only: bool,
+}
+
+macro_rules! directives {
+  ($($(#[$doc:meta])* $method:ident => $name:literal,)*) => {
+    $(
+      $(#[$doc])*
+      pub fn $method(self, sources: impl IntoIterator<Item = Source>) -> Self {
+        self.directive($name, sources)
+      }
+    )*
+  };
+}
+
+impl ContentSecurityPolicy {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  directives! {
+    /// The fallback for the other fetch directives.
+    default_src => "default-src",
+    script_src => "script-src",
+    style_src => "style-src",
+    img_src => "img-src",
+    connect_src => "connect-src",
+    font_src => "font-src",
+    object_src => "object-src",
+    media_src => "media-src",
+    frame_src => "frame-src",
+    worker_src => "worker-src",
+    manifest_src => "manifest-src",
+    /// The origins that may embed the page, superseding `X-Frame-Options`.
+    frame_ancestors => "frame-ancestors",
+    base_uri => "base-uri",
+    form_action => "form-action",
+  }
+
+  /// Sets a directive by name, replacing any sources it was set to.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `name` is not a directive name, or if a [`Source::Host`] or
+  /// [`Source::Scheme`] is empty or contains whitespace, quotes, `;` or `,`.
+  pub fn directive(
+    mut self,
+    name: &'static str,
+    sources: impl IntoIterator<Item = Source>,
+  ) -> Self {
+    assert!(
+      !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-'),
+      "invalid CSP directive name `{}`",
+      name.escape_debug()
+    );
+
+    let sources: Vec<Source> = sources.into_iter().collect();
+    sources.iter().for_each(Source::validate);
+
+    match self.directives.iter_mut().find(|(n, _)| *n == name) {
+      Some((_, existing)) => *existing = sources,
+      None => self.directives.push((name, sources)),
+    }
+    self
+  }
+
+  /// Tells browsers to load `http` resources over `https`.
+  pub fn upgrade_insecure_requests(mut self) -> Self {
+    self.upgrade_insecure_requests = true;
+    self
+  }
+
+  /// Sets the uri violations are reported to.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `uri` is empty or contains whitespace, quotes, `;` or `,`.
+  pub fn report_uri(mut self, uri: impl Into<String>) -> Self {
+    let uri = uri.into();
+    assert!(
+      is_token(&uri),
+      "invalid CSP report uri `{}`: the uri cannot be empty or contain whitespace, quotes, `;` or `,`",
+      uri.escape_debug()
+    );
+    self.report_uri = Some(uri);
+    self
+  }
+
+  /// Sends the policy in `Content-Security-Policy-Report-Only`, so that
+  /// violations are reported but not enforced.
+  pub fn report_only(mut self) -> Self {
+    self.report_only = true;
+    self
+  }
+
+  fn uses_nonce(&self) -> bool {
+    self
+      .directives
+      .iter()
+      .any(|(_, sources)| sources.contains(&Source::Nonce))
+  }
+
+  fn header_name(&self) -> HeaderName {
+    if self.report_only {
+      HeaderName::from_static("content-security-policy-report-only")
+    } else {
+      HeaderName::from_static("content-security-policy")
+    }
+  }
+
+  fn to_header(&self, nonce: Option<&str>) -> HeaderValue {
+    let mut out = String::new();
+
+    for (name, sources) in &self.directives {
+      if !out.is_empty() {
+        out.push_str("; ");
+      }
+      out.push_str(name);
+      for source in sources {
+        out.push(' ');
+        source.write(nonce, &mut out);
+      }
+    }
+
+    if self.upgrade_insecure_requests {
+      if !out.is_empty() {
+        out.push_str("; ");
+      }
+      out.push_str("upgrade-insecure-requests");
+    }
+
+    if let Some(uri) = &self.report_uri {
+      if !out.is_empty() {
+        out.push_str("; ");
+      }
+      out.push_str("report-uri ");
+      out.push_str(uri);
+    }
+
+    HeaderValue::from_str(&out).expect("CSP sources are validated by the builder")
+  }
+}
+
+/// Sets security related headers on every response.
+///
+/// The defaults are:
+///
+/// - `Strict-Transport-Security: max-age=31536000; includeSubDomains`
+/// - `X-Content-Type-Options: nosniff`
+/// - `X-Frame-Options: DENY`
+/// - `Referrer-Policy: strict-origin-when-cross-origin`
+/// - `Permissions-Policy: camera=(), geolocation=(), microphone=()`
+/// - `Cross-Origin-Opener-Policy: same-origin`
+///
+/// No `Content-Security-Policy` is sent unless one is set with
+/// [`content_security_policy`](SecurityHeaders::content_security_policy),
+/// and no `Cross-Origin-Embedder-Policy` unless one is set with
+/// [`embedder_policy`](SecurityHeaders::embedder_policy), since
+/// `require-corp` blocks cross-origin resources that don't opt in.
+/// Headers already set by the handler are left as is, so they can be
+/// overridden per response. Every header can be disabled by passing
+/// `None`.
+///
+/// ```rust
+/// use turbofish::middleware::security_headers::{ContentSecurityPolicy, FrameOptions, SecurityHeaders, Source};
+/// use turbofish::router::Router;
+///
+/// let mut router = Router::default();
+/// router.middleware(
+///   SecurityHeaders::new()
+///     .frame_options(FrameOptions::SameOrigin)
+///     .content_security_policy(ContentSecurityPolicy::new().default_src(vec![Source::SelfOrigin])),
+/// );
+/// ```
+pub struct SecurityHeaders {
+  hsts: Option<Hsts>,
+  no_sniff: bool,
+  frame_options: Option<FrameOptions>,
+  referrer_policy: Option<ReferrerPolicy>,
+  permissions_policy: Option<HeaderValue>,
+  opener_policy: Option<OpenerPolicy>,
+  embedder_policy: Option<EmbedderPolicy>,
+  csp: Option<ContentSecurityPolicy>,
+}
+
+impl Default for SecurityHeaders {
+  fn default() -> Self {
+    Self {
+      hsts: Some(Hsts::default()),
+      no_sniff: true,
+      frame_options: Some(FrameOptions::Deny),
+      referrer_policy: Some(ReferrerPolicy::StrictOriginWhenCrossOrigin),
+      permissions_policy: Some(HeaderValue::from_static(
+        "camera=(), geolocation=(), microphone=()",
+      )),
+      opener_policy: Some(OpenerPolicy::SameOrigin),
+      embedder_policy: None,
+      csp: None,
+    }
+  }
+}
+
+impl SecurityHeaders {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn hsts(mut self, hsts: impl Into<Option<Hsts>>) -> Self {
+    self.hsts = hsts.into();
+    self
+  }
+
+  /// Sets whether to send `X-Content-Type-Options: nosniff`.
+  pub fn no_sniff(mut self, no_sniff: bool) -> Self {
+    self.no_sniff = no_sniff;
+    self
+  }
+
+  pub fn frame_options(mut self, options: impl Into<Option<FrameOptions>>) -> Self {
+    self.frame_options = options.into();
+    self
+  }
+
+  pub fn referrer_policy(mut self, policy: impl Into<Option<ReferrerPolicy>>) -> Self {
+    self.referrer_policy = policy.into();
+    self
+  }
+
+  /// Sets the `Permissions-Policy` header, ex: `camera=(), fullscreen=(self)`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `policy` is not a valid header value.
+  pub fn permissions_policy(mut self, policy: impl Into<Option<&'static str>>) -> Self {
+    self.permissions_policy = policy.into().map(HeaderValue::from_static);
+    self
+  }
+
+  pub fn opener_policy(mut self, policy: impl Into<Option<OpenerPolicy>>) -> Self {
+    self.opener_policy = policy.into();
+    self
+  }
+
+  /// Sets the `Cross-Origin-Embedder-Policy` header (default is none).
+  pub fn embedder_policy(mut self, policy: impl Into<Option<EmbedderPolicy>>) -> Self {
+    self.embedder_policy = policy.into();
+    self
+  }
+
+  /// Sets the `Content-Security-Policy`. If the policy allows
+  /// [`Source::Nonce`], a new [`CspNonce`] is generated for every request
+  /// and inserted into the request extensions.
+  pub fn content_security_policy(mut self, csp: impl Into<Option<ContentSecurityPolicy>>) -> Self {
+    self.csp = csp.into();
+    self
+  }
+
+  fn apply(&self, headers: &mut HeaderMap, nonce: Option<&str>) {
+    let mut set = |name: HeaderName, value: HeaderValue| {
+      if !headers.contains_key(&name) {
+        headers.insert(name, value);
+      }
+    };
+
+    if let Some(hsts) = &self.hsts {
+      set(header::STRICT_TRANSPORT_SECURITY, hsts.to_header());
+    }
+
+    if self.no_sniff {
+      set(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+      );
+    }
+
+    if let Some(options) = self.frame_options {
+      set(
+        header::X_FRAME_OPTIONS,
+        HeaderValue::from_static(match options {
+          FrameOptions::Deny => "DENY",
+          FrameOptions::SameOrigin => "SAMEORIGIN",
+        }),
+      );
+    }
+
+    if let Some(policy) = self.referrer_policy {
+      set(
+        header::REFERRER_POLICY,
+        HeaderValue::from_static(policy.as_str()),
+      );
+    }
+
+    if let Some(policy) = &self.permissions_policy {
+      set(
+        HeaderName::from_static("permissions-policy"),
+        policy.clone(),
+      );
+    }
+
+    if let Some(policy) = self.opener_policy {
+      set(
+        HeaderName::from_static("cross-origin-opener-policy"),
+        HeaderValue::from_static(match policy {
+          OpenerPolicy::SameOrigin => "same-origin",
+          OpenerPolicy::SameOriginAllowPopups => "same-origin-allow-popups",
+          OpenerPolicy::UnsafeNone => "unsafe-none",
+        }),
+      );
+    }
+
+    if let Some(policy) = self.embedder_policy {
+      set(
+        HeaderName::from_static("cross-origin-embedder-policy"),
+        HeaderValue::from_static(match policy {
+          EmbedderPolicy::RequireCorp => "require-corp",
+          EmbedderPolicy::Credentialless => "credentialless",
+          EmbedderPolicy::UnsafeNone => "unsafe-none",
+        }),
+      );
+    }
+
+    if let Some(csp) = &self.csp {
+      set(csp.header_name(), csp.to_header(nonce));
+    }
+  }
+}
+
+#[crate::async_trait]
+impl Middleware for SecurityHeaders {
+  async fn call(&self, mut req: Request, next: Next<'_>) -> Response {
+    let nonce = match &self.csp {
+      Some(csp) if csp.uses_nonce() => {
+        let mut bytes = [0; 16];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let nonce: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        req.extensions_mut().insert(CspNonce(nonce.clone()));
+        Some(nonce)
+      }
+      _ => None,
+    };
+
+    let mut res = next.run(req).await;
+    self.apply(res.headers_mut(), nonce.as_deref());
+    res
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::Config;
+  use crate::http::Method;
+  use crate::router::{Route, Router};
+
+  #[tokio::test]
+  async fn security_headers() {
+    let mut router = Router::default();
+    router.route(Route::new(Method::GET, "/", |req: Request| async move {
+      let nonce = req.extensions().get::<CspNonce>().unwrap().clone();
+      Response::builder()
+        .header("x-nonce", nonce.as_str())
+        .header(header::X_FRAME_OPTIONS, "SAMEORIGIN")
+        .body("")
+        .unwrap()
+    }));
+    router.middleware(
+      SecurityHeaders::new().content_security_policy(
+        ContentSecurityPolicy::new()
+          .default_src(vec![Source::SelfOrigin])
+          .script_src(vec![Source::SelfOrigin, Source::Nonce])
+          .upgrade_insecure_requests(),
+      ),
+    );
+
+    let req = hyper::Request::builder()
+      .uri("/")
+      .body(hyper::Body::empty())
+      .unwrap();
+    let res = router.serve(req.into(), &Config::default()).await.unwrap();
+    let headers = res.headers();
+
+    assert_eq!(
+      headers["strict-transport-security"],
+      "max-age=31536000; includeSubDomains"
+    );
+    assert_eq!(headers["x-content-type-options"], "nosniff");
+    assert_eq!(headers["x-frame-options"], "SAMEORIGIN");
+    assert_eq!(
+      headers["referrer-policy"],
+      "strict-origin-when-cross-origin"
+    );
+    assert_eq!(headers["cross-origin-opener-policy"], "same-origin");
+    assert!(!headers.contains_key("cross-origin-embedder-policy"));
+
+    let nonce = headers["x-nonce"].to_str().unwrap();
+    assert_eq!(
+      headers["content-security-policy"],
+      format!(
+        "default-src 'self'; script-src 'self' 'nonce-{}'; upgrade-insecure-requests",
+        nonce
+      )
+      .as_str()
+    );
+  }
+
+  #[test]
+  fn csp_sources() {
+    let csp = ContentSecurityPolicy::new()
+      .img_src(vec![
+        Source::Host("https://*.example.com:443/img/".into()),
+        Source::Scheme("data:".into()),
+      ])
+      .report_uri("/csp-reports");
+    assert_eq!(
+      csp.to_header(None),
+      "img-src https://*.example.com:443/img/ data:; report-uri /csp-reports"
+    );
+
+    for source in &[
+      "example.com; script-src *",
+      "a.com,b.com",
+      "a.com\r\nx-injected: 1",
+      "'unsafe-inline'",
+      "",
+    ] {
+      let result = std::panic::catch_unwind(|| {
+        ContentSecurityPolicy::new().script_src(vec![Source::Host(source.to_string())])
+      });
+      assert!(result.is_err(), "{:?} was accepted", source);
+    }
+
+    let result =
+      std::panic::catch_unwind(|| ContentSecurityPolicy::new().report_uri("/r; script-src *"));
+    assert!(result.is_err());
+  }
+}
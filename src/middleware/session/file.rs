@@ -0,0 +1,243 @@
+use super::{now, Data, SessionStore};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A [`SessionStore`] that keeps each session in a JSON file in a
+/// directory.
+///
+/// Expired sessions are removed when they are next loaded, and by a sweep
+/// of the directory that runs on save at most once a minute, or when
+/// [`sweep`](FileStore::sweep) is called.
+pub struct FileStore {
+  dir: PathBuf,
+  last_sweep: Mutex<Instant>,
+}
+
+// how often expired sessions are swept on save
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+  /// Expiry time in seconds since the unix epoch.
+  expires: u64,
+  data: Data,
+}
+
+impl FileStore {
+  /// Creates a store that keeps sessions in `dir`, which is created if it
+  /// does not exist.
+  pub fn new(dir: impl Into<PathBuf>) -> Self {
+    Self {
+      dir: dir.into(),
+      last_sweep: Mutex::new(Instant::now()),
+    }
+  }
+
+  /// Removes the files of expired sessions.
+  pub async fn sweep(&self) -> io::Result<()> {
+    let mut entries = match tokio::fs::read_dir(&self.dir).await {
+      Ok(entries) => entries,
+      Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+      Err(err) => return Err(err),
+    };
+
+    let now = now();
+    while let Some(entry) = entries.next_entry().await? {
+      let path = entry.path();
+      if path.extension() != Some("json".as_ref()) {
+        continue;
+      }
+
+      // files that can't be read were removed concurrently or aren't ours
+      let expired = match tokio::fs::read(&path).await {
+        Ok(contents) => matches!(
+          serde_json::from_slice::<Entry>(&contents),
+          Ok(entry) if entry.expires <= now
+        ),
+        Err(_) => false,
+      };
+
+      if expired {
+        match tokio::fs::remove_file(&path).await {
+          Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+          _ => {}
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  fn should_sweep(&self) -> bool {
+    let mut last_sweep = self.last_sweep.lock().unwrap();
+    if last_sweep.elapsed() > SWEEP_INTERVAL {
+      *last_sweep = Instant::now();
+      true
+    } else {
+      false
+    }
+  }
+
+  fn path(&self, id: &str) -> io::Result<PathBuf> {
+    // IDs come from signed cookies, but never let them escape the directory
+    if id.is_empty() || !id.bytes().all(|b| b.is_ascii_alphanumeric()) {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "invalid session id",
+      ));
+    }
+
+    Ok(self.dir.join(format!("{}.json", id)))
+  }
+}
+
+#[crate::async_trait]
+impl SessionStore for FileStore {
+  async fn load(&self, id: &str) -> Result<Option<Data>, Box<dyn Error + Send + Sync>> {
+    let path = self.path(id)?;
+
+    let contents = match tokio::fs::read(&path).await {
+      Ok(contents) => contents,
+      Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+      Err(err) => return Err(err.into()),
+    };
+
+    let entry: Entry = serde_json::from_slice(&contents)?;
+    if entry.expires <= now() {
+      self.destroy(id).await?;
+      return Ok(None);
+    }
+
+    Ok(Some(entry.data))
+  }
+
+  async fn save(
+    &self,
+    id: &str,
+    data: &Data,
+    ttl: Duration,
+  ) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let path = self.path(id)?;
+    let entry = Entry {
+      expires: now() + ttl.as_secs(),
+      data: data.clone(),
+    };
+
+    tokio::fs::create_dir_all(&self.dir).await?;
+
+    if self.should_sweep() {
+      self.sweep().await?;
+    }
+
+    // write to a temporary file first so concurrent loads never see a
+    // partially written session, named uniquely so concurrent saves of the
+    // same session don't write to the same file
+    let tmp = self
+      .dir
+      .join(format!("{}.{:016x}.tmp", id, rand::random::<u64>()));
+    tokio::fs::write(&tmp, serde_json::to_vec(&entry)?).await?;
+    if let Err(err) = tokio::fs::rename(&tmp, &path).await {
+      let _ = tokio::fs::remove_file(&tmp).await;
+      return Err(err.into());
+    }
+
+    Ok(())
+  }
+
+  async fn destroy(&self, id: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    match tokio::fs::remove_file(self.path(id)?).await {
+      Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+      _ => Ok(()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn roundtrip() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = FileStore::new(dir.path().join("sessions"));
+
+    let mut data = Data::new();
+    data.insert("user_id".to_owned(), 1.into());
+
+    store
+      .save("abc", &data, Duration::from_secs(60))
+      .await
+      .unwrap();
+    assert_eq!(store.load("abc").await.unwrap(), Some(data.clone()));
+
+    store
+      .save("expired", &data, Duration::from_secs(0))
+      .await
+      .unwrap();
+    assert_eq!(store.load("expired").await.unwrap(), None);
+    assert!(!dir.path().join("sessions/expired.json").exists());
+
+    store.destroy("abc").await.unwrap();
+    assert_eq!(store.load("abc").await.unwrap(), None);
+    assert!(store.load("../abc").await.is_err());
+  }
+
+  #[tokio::test]
+  async fn sweep() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = FileStore::new(dir.path());
+    store.sweep().await.unwrap();
+
+    let data = Data::new();
+    store
+      .save("live", &data, Duration::from_secs(60))
+      .await
+      .unwrap();
+    store
+      .save("expired", &data, Duration::from_secs(0))
+      .await
+      .unwrap();
+    std::fs::write(dir.path().join("other.txt"), "").unwrap();
+
+    store.sweep().await.unwrap();
+    assert!(dir.path().join("live.json").exists());
+    assert!(!dir.path().join("expired.json").exists());
+    assert!(dir.path().join("other.txt").exists());
+
+    // saves sweep once the interval has passed
+    store
+      .save("expired", &data, Duration::from_secs(0))
+      .await
+      .unwrap();
+    *store.last_sweep.lock().unwrap() -= SWEEP_INTERVAL * 2;
+    store
+      .save("abc", &data, Duration::from_secs(60))
+      .await
+      .unwrap();
+    assert!(!dir.path().join("expired.json").exists());
+  }
+
+  #[tokio::test]
+  async fn concurrent_saves() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = FileStore::new(dir.path());
+
+    let saves = (0..16).map(|i| {
+      let mut data = Data::new();
+      data.insert("n".to_owned(), i.into());
+      let store = &store;
+      async move { store.save("abc", &data, Duration::from_secs(60)).await }
+    });
+
+    for result in futures::future::join_all(saves).await {
+      result.unwrap();
+    }
+
+    assert!(store.load("abc").await.unwrap().is_some());
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+  }
+}
@@ -0,0 +1,72 @@
+use super::{Data, SessionStore};
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// An in-process [`SessionStore`]. Sessions are lost when the process
+/// exits.
+pub struct MemoryStore {
+  inner: Mutex<Inner>,
+}
+
+struct Inner {
+  sessions: HashMap<String, (Data, Instant)>,
+  last_sweep: Instant,
+}
+
+// how often expired sessions are dropped
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+impl Default for MemoryStore {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl MemoryStore {
+  pub fn new() -> Self {
+    Self {
+      inner: Mutex::new(Inner {
+        sessions: HashMap::new(),
+        last_sweep: Instant::now(),
+      }),
+    }
+  }
+}
+
+#[crate::async_trait]
+impl SessionStore for MemoryStore {
+  async fn load(&self, id: &str) -> Result<Option<Data>, Box<dyn Error + Send + Sync>> {
+    let inner = self.inner.lock().unwrap();
+    Ok(match inner.sessions.get(id) {
+      Some((data, expires)) if *expires > Instant::now() => Some(data.clone()),
+      _ => None,
+    })
+  }
+
+  async fn save(
+    &self,
+    id: &str,
+    data: &Data,
+    ttl: Duration,
+  ) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut inner = self.inner.lock().unwrap();
+    let now = Instant::now();
+
+    if now.duration_since(inner.last_sweep) > SWEEP_INTERVAL {
+      inner.sessions.retain(|_, (_, expires)| *expires > now);
+      inner.last_sweep = now;
+    }
+
+    inner
+      .sessions
+      .insert(id.to_owned(), (data.clone(), now + ttl));
+    Ok(())
+  }
+
+  async fn destroy(&self, id: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    self.inner.lock().unwrap().sessions.remove(id);
+    Ok(())
+  }
+}
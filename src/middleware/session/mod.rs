@@ -0,0 +1,572 @@
+mod file;
+mod memory;
+
+pub use file::FileStore;
+pub use memory::MemoryStore;
+
+#[doc(no_inline)]
+pub use cookie::SameSite;
+
+use crate::http::{Body, Cookie, CookieJar, Request, Response, StatusCode};
+use crate::middleware::{Middleware, Next};
+use rand::RngCore;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The values stored in a session.
+pub type Data = serde_json::Map<String, serde_json::Value>;
+
+/// Storage for server-side sessions.
+///
+/// Sessions are identified by a random ID stored in a signed cookie. The
+/// [`MemoryStore`] keeps sessions in process and the [`FileStore`] keeps
+/// them on disk. Implement this trait to share sessions between
+/// processes, for example in Redis.
+#[crate::async_trait]
+pub trait SessionStore: Send + Sync {
+  /// Loads the session with the given ID, if it exists and has not expired.
+  async fn load(&self, id: &str) -> Result<Option<Data>, Box<dyn Error + Send + Sync>>;
+
+  /// Stores the session with the given ID, expiring it after `ttl`.
+  async fn save(
+    &self,
+    id: &str,
+    data: &Data,
+    ttl: Duration,
+  ) -> Result<(), Box<dyn Error + Send + Sync>>;
+
+  /// Removes the session with the given ID.
+  async fn destroy(&self, id: &str) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
+
+/// The session of the current request.
+///
+/// The [`Sessions`] middleware inserts the session into the request
+/// extensions, and persists any changes after the rest of the stack has
+/// run:
+///
+/// ```rust
+/// use turbofish::http::{Request, Response};
+/// use turbofish::middleware::session::Session;
+///
+/// async fn login(req: Request) -> Response {
+///   let session = req.extensions().get::<Session>().unwrap();
+///   // give the user a new session ID now that they are logged in
+///   session.renew();
+///   session.insert("user_id", 1).unwrap();
+///   Response::new("logged in")
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct Session {
+  state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+  data: Data,
+  changed: bool,
+  renewed: bool,
+  destroyed: bool,
+}
+
+impl Session {
+  fn new(data: Data) -> Self {
+    Self {
+      state: Arc::new(Mutex::new(State {
+        data,
+        ..State::default()
+      })),
+    }
+  }
+
+  /// Returns the value of `key`, or `None` if it is not set or cannot be
+  /// deserialized as `T`.
+  pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+    let state = self.state.lock().unwrap();
+    let value = state.data.get(key)?.clone();
+    serde_json::from_value(value).ok()
+  }
+
+  /// Sets `key` to `value`.
+  pub fn insert<T: Serialize>(
+    &self,
+    key: impl Into<String>,
+    value: T,
+  ) -> Result<(), serde_json::Error> {
+    let value = serde_json::to_value(value)?;
+    let mut state = self.state.lock().unwrap();
+    state.data.insert(key.into(), value);
+    state.changed = true;
+    Ok(())
+  }
+
+  /// Removes `key`, returning whether it was set.
+  pub fn remove(&self, key: &str) -> bool {
+    let mut state = self.state.lock().unwrap();
+    let removed = state.data.remove(key).is_some();
+    state.changed |= removed;
+    removed
+  }
+
+  /// Removes every value from the session, keeping its ID.
+  pub fn clear(&self) {
+    let mut state = self.state.lock().unwrap();
+    state.changed |= !state.data.is_empty();
+    state.data.clear();
+  }
+
+  /// Moves the session to a new ID, keeping its values.
+  ///
+  /// This should be called whenever the privileges of the session change,
+  /// ex: on login or logout, to prevent session fixation attacks.
+  pub fn renew(&self) {
+    self.state.lock().unwrap().renewed = true;
+  }
+
+  /// Removes the session from the store and its cookie from the client.
+  /// Values inserted afterwards are discarded.
+  pub fn destroy(&self) {
+    let mut state = self.state.lock().unwrap();
+    state.data.clear();
+    state.destroyed = true;
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.state.lock().unwrap().data.is_empty()
+  }
+
+  fn take(&self) -> State {
+    std::mem::take(&mut *self.state.lock().unwrap())
+  }
+}
+
+enum Backend {
+  Store(Arc<dyn SessionStore>),
+  Cookie,
+}
+
+/// The contents of a client-side session cookie. The expiry is kept inside
+/// the encrypted value, so an old cookie can't be replayed once the
+/// session has expired even if the client ignores `Max-Age`.
+#[derive(Serialize, Deserialize)]
+struct Payload<D> {
+  /// Expiry time in seconds since the unix epoch.
+  expires: u64,
+  data: D,
+}
+
+/// Session middleware.
+///
+/// With [`Sessions::new`], session values are kept in a [`SessionStore`]
+/// and the client only holds the session ID, in a cookie signed with the
+/// [`secret_key`](crate::config::Config::secret_key). With
+/// [`Sessions::cookie`], the values themselves are kept in a cookie
+/// encrypted with the key, so no store is needed but sessions are limited
+/// to the ~4KB browsers allow per cookie and cannot be revoked before they
+/// expire. Without a secret key, requests are answered with
+/// `500 Internal Server Error`.
+///
+/// ```rust
+/// use turbofish::middleware::session::{MemoryStore, Sessions};
+/// use turbofish::router::Router;
+///
+/// let mut router = Router::default();
+/// router.middleware(Sessions::new(MemoryStore::new()).cookie_name("sid"));
+/// ```
+pub struct Sessions {
+  backend: Backend,
+  cookie_name: String,
+  path: String,
+  domain: Option<String>,
+  secure: bool,
+  same_site: SameSite,
+  ttl: Duration,
+}
+
+impl Sessions {
+  /// Creates a middleware that keeps sessions in `store`.
+  pub fn new(store: impl SessionStore + 'static) -> Self {
+    Self::with_backend(Backend::Store(Arc::new(store)))
+  }
+
+  /// Creates a middleware that keeps sessions entirely in a cookie.
+  pub fn cookie() -> Self {
+    Self::with_backend(Backend::Cookie)
+  }
+
+  fn with_backend(backend: Backend) -> Self {
+    Self {
+      backend,
+      cookie_name: "session".to_owned(),
+      path: "/".to_owned(),
+      domain: None,
+      secure: true,
+      same_site: SameSite::Lax,
+      ttl: Duration::from_secs(60 * 60 * 24),
+    }
+  }
+
+  /// Sets the name of the session cookie (default is `session`).
+  pub fn cookie_name(mut self, name: impl Into<String>) -> Self {
+    self.cookie_name = name.into();
+    self
+  }
+
+  /// Sets the path of the session cookie (default is `/`).
+  pub fn path(mut self, path: impl Into<String>) -> Self {
+    self.path = path.into();
+    self
+  }
+
+  /// Sets the domain of the session cookie (default is none, which
+  /// restricts it to the current host).
+  pub fn domain(mut self, domain: impl Into<String>) -> Self {
+    self.domain = Some(domain.into());
+    self
+  }
+
+  /// Sets whether the session cookie is only sent over HTTPS (default is
+  /// `true`).
+  pub fn secure(mut self, secure: bool) -> Self {
+    self.secure = secure;
+    self
+  }
+
+  /// Sets the `SameSite` attribute of the session cookie (default is `Lax`).
+  pub fn same_site(mut self, same_site: SameSite) -> Self {
+    self.same_site = same_site;
+    self
+  }
+
+  /// Sets how long a session lives after it was last modified (default is
+  /// one day).
+  pub fn ttl(mut self, ttl: Duration) -> Self {
+    self.ttl = ttl;
+    self
+  }
+
+  fn build_cookie(&self, value: String) -> Cookie<'static> {
+    let mut cookie = Cookie::build(self.cookie_name.clone(), value)
+      .path(self.path.clone())
+      .http_only(true)
+      .secure(self.secure)
+      .same_site(self.same_site)
+      .max_age(time::Duration::seconds(self.ttl.as_secs() as i64))
+      .finish();
+
+    if let Some(domain) = &self.domain {
+      cookie.set_domain(domain.clone());
+    }
+
+    cookie
+  }
+
+  fn removal_cookie(&self) -> Cookie<'static> {
+    let mut cookie = Cookie::named(self.cookie_name.clone());
+    cookie.set_path(self.path.clone());
+    if let Some(domain) = &self.domain {
+      cookie.set_domain(domain.clone());
+    }
+    cookie
+  }
+
+  async fn load(&self, jar: &CookieJar) -> (Option<String>, Data) {
+    match &self.backend {
+      Backend::Store(store) => {
+        let id = match jar.signed().and_then(|jar| jar.get(&self.cookie_name)) {
+          Some(cookie) => cookie.value().to_owned(),
+          None => return (None, Data::new()),
+        };
+
+        // IDs of expired or unknown sessions are never reused, so a client
+        // cannot pick the ID of the next session (session fixation)
+        match store.load(&id).await {
+          Ok(Some(data)) => (Some(id), data),
+          Ok(None) => (None, Data::new()),
+          Err(err) => {
+            log::error!("failed to load session: {}", err);
+            (None, Data::new())
+          }
+        }
+      }
+      Backend::Cookie => {
+        let data = jar
+          .private()
+          .and_then(|jar| jar.get(&self.cookie_name))
+          .and_then(|cookie| serde_json::from_str::<Payload<Data>>(cookie.value()).ok())
+          .filter(|payload| payload.expires > now())
+          .map(|payload| payload.data);
+
+        // client-side sessions have no ID, but the cookie still needs to
+        // be removed when the session is destroyed
+        match data {
+          Some(data) => (Some(String::new()), data),
+          None => (None, Data::new()),
+        }
+      }
+    }
+  }
+
+  /// Persists the session, updating the cookie in `jar` if needed.
+  async fn save(&self, jar: &CookieJar, id: Option<String>, state: State) {
+    if state.destroyed || (state.changed && state.data.is_empty()) {
+      if let (Backend::Store(store), Some(id)) = (&self.backend, &id) {
+        if let Err(err) = store.destroy(id).await {
+          log::error!("failed to destroy session: {}", err);
+        }
+      }
+
+      if id.is_some() {
+        jar.remove(self.removal_cookie());
+      }
+
+      return;
+    }
+
+    let store = match &self.backend {
+      Backend::Store(store) => store,
+      Backend::Cookie => {
+        if state.changed {
+          let payload = Payload {
+            expires: now() + self.ttl.as_secs(),
+            data: &state.data,
+          };
+
+          match (serde_json::to_string(&payload), jar.private()) {
+            (Ok(value), Some(jar)) => jar.add(self.build_cookie(value)),
+            (Err(err), _) => log::error!("failed to serialize session: {}", err),
+            (_, None) => {}
+          }
+        }
+        return;
+      }
+    };
+
+    let new_id = match id {
+      Some(id) if state.renewed => {
+        if let Err(err) = store.destroy(&id).await {
+          log::error!("failed to destroy session: {}", err);
+        }
+        generate_id()
+      }
+      Some(id) if state.changed => id,
+      Some(_) => return,
+      // empty sessions are not stored
+      None if state.data.is_empty() => return,
+      None => generate_id(),
+    };
+
+    if let Err(err) = store.save(&new_id, &state.data, self.ttl).await {
+      log::error!("failed to save session: {}", err);
+      return;
+    }
+
+    // resend the cookie to extend its lifetime along with the session
+    if let Some(jar) = jar.signed() {
+      jar.add(self.build_cookie(new_id));
+    }
+  }
+}
+
+#[crate::async_trait]
+impl Middleware for Sessions {
+  async fn call(&self, mut req: Request, next: Next<'_>) -> Response {
+    // changes to the jar are sent as `Set-Cookie` headers by the router
+    let jar = req.cookies().clone();
+    if jar.signed().is_none() {
+      log::error!("sessions require `Config::secret_key` to be set");
+      return Response::builder()
+        .status(StatusCode::INTERNAL_SERVER_ERROR)
+        .body(Body::empty())
+        .unwrap();
+    }
+
+    let (id, data) = self.load(&jar).await;
+    let session = Session::new(data);
+    req.extensions_mut().insert(session.clone());
+
+    let res = next.run(req).await;
+    self.save(&jar, id, session.take()).await;
+    res
+  }
+}
+
+/// Returns the current time in seconds since the unix epoch.
+fn now() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(0)
+}
+
+/// Returns a random 256-bit session ID, hex encoded.
+fn generate_id() -> String {
+  let mut bytes = [0; 32];
+  rand::thread_rng().fill_bytes(&mut bytes);
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::Config;
+  use crate::http::{header, Key, Method};
+  use crate::router::{Route, Router};
+
+  fn router(sessions: Sessions) -> Router {
+    let mut router = Router::default();
+    router.route(Route::new(
+      Method::GET,
+      "/count",
+      |req: Request| async move {
+        let session = req.extensions().get::<Session>().unwrap();
+        let count = session.get::<u64>("count").unwrap_or(0) + 1;
+        session.insert("count", count).unwrap();
+        Response::builder()
+          .header("x-count", count)
+          .body("")
+          .unwrap()
+      },
+    ));
+    router.route(Route::new(
+      Method::GET,
+      "/login",
+      |req: Request| async move {
+        req.extensions().get::<Session>().unwrap().renew();
+        Response::new("")
+      },
+    ));
+    router.route(Route::new(
+      Method::GET,
+      "/logout",
+      |req: Request| async move {
+        req.extensions().get::<Session>().unwrap().destroy();
+        Response::new("")
+      },
+    ));
+    router.middleware(sessions);
+    router
+  }
+
+  async fn get(
+    router: &Router,
+    config: &Config,
+    path: &str,
+    cookie: Option<&str>,
+  ) -> (Response, Option<String>) {
+    let mut req = hyper::Request::builder().uri(path);
+    if let Some(cookie) = cookie {
+      req = req.header(header::COOKIE, cookie);
+    }
+
+    let res = router
+      .serve(req.body(hyper::Body::empty()).unwrap().into(), config)
+      .await
+      .unwrap();
+
+    let cookie = res.headers().get(header::SET_COOKIE).map(|value| {
+      let value = value.to_str().unwrap();
+      value.split(';').next().unwrap().to_owned()
+    });
+
+    (res, cookie)
+  }
+
+  #[tokio::test]
+  async fn store() {
+    let router = router(Sessions::new(MemoryStore::new()));
+    let config = Config::default().secret_key(Key::generate());
+
+    let (res, cookie) = get(&router, &config, "/count", None).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let cookie = cookie.unwrap();
+
+    let (res, _) = get(&router, &config, "/count", Some(&cookie)).await;
+    assert_eq!(res.headers()["x-count"], "2");
+    let (_, renewed) = get(&router, &config, "/login", Some(&cookie)).await;
+    let renewed = renewed.unwrap();
+    assert_ne!(renewed, cookie);
+
+    // the old ID no longer refers to the session
+    let (res, _) = get(&router, &config, "/count", Some(&cookie)).await;
+    assert_eq!(res.headers()["x-count"], "1");
+
+    let (res, same) = get(&router, &config, "/count", Some(&renewed)).await;
+    assert_eq!(res.headers()["x-count"], "3");
+    assert_eq!(same.as_ref(), Some(&renewed));
+
+    let (_, removal) = get(&router, &config, "/logout", Some(&renewed)).await;
+    assert_eq!(removal.unwrap(), "session=");
+
+    // tampered IDs are ignored
+    let (_, cookie) = get(&router, &config, "/count", Some("session=abc")).await;
+    assert_ne!(cookie.unwrap(), "session=abc");
+  }
+
+  #[tokio::test]
+  async fn unknown_id() {
+    let router = router(Sessions::new(MemoryStore::new()));
+    let config = Config::default().secret_key(Key::generate());
+
+    // a validly signed ID that the store does not know, ex: set by an
+    // attacker on a subdomain
+    let mut jar = cookie::CookieJar::new();
+    jar
+      .signed(config.secret_key.as_ref().unwrap())
+      .add(Cookie::new("session", "fixated"));
+    let fixated = jar.get("session").unwrap().to_string();
+
+    let (res, cookie) = get(&router, &config, "/count", Some(&fixated)).await;
+    assert_eq!(res.headers()["x-count"], "1");
+    assert_ne!(cookie.unwrap(), fixated);
+
+    // the session was not stored under the client's ID
+    let (res, _) = get(&router, &config, "/count", Some(&fixated)).await;
+    assert_eq!(res.headers()["x-count"], "1");
+  }
+
+  #[tokio::test]
+  async fn client_side() {
+    let router = router(Sessions::cookie());
+    let config = Config::default().secret_key(Key::generate());
+
+    let (_, cookie) = get(&router, &config, "/count", None).await;
+    let (res, cookie) = get(&router, &config, "/count", cookie.as_deref()).await;
+    assert_eq!(res.headers()["x-count"], "2");
+    let cookie = cookie.unwrap();
+
+    let (_, removal) = get(&router, &config, "/logout", Some(&cookie)).await;
+    assert_eq!(removal.unwrap(), "session=");
+  }
+
+  #[tokio::test]
+  async fn client_side_expired() {
+    let router = router(Sessions::cookie());
+    let config = Config::default().secret_key(Key::generate());
+
+    // a genuine cookie replayed after the session expired
+    let mut jar = cookie::CookieJar::new();
+    jar
+      .private(config.secret_key.as_ref().unwrap())
+      .add(Cookie::new(
+        "session",
+        r#"{"expires":1,"data":{"count":5}}"#,
+      ));
+    let expired = jar.get("session").unwrap().to_string();
+
+    let (res, _) = get(&router, &config, "/count", Some(&expired)).await;
+    assert_eq!(res.headers()["x-count"], "1");
+  }
+
+  #[tokio::test]
+  async fn without_secret_key() {
+    let router = router(Sessions::new(MemoryStore::new()));
+    let (res, cookie) = get(&router, &Config::default(), "/count", None).await;
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(cookie.is_none());
+  }
+}
@@ -0,0 +1,202 @@
+use super::Params;
+use crate::http::Request;
+use std::fmt;
+
+/// A role that can be required on a route with [`Route::require`](super::Route::require).
+///
+/// The principal is taken from the request extensions, where it is
+/// inserted by the [`Authenticate`](crate::middleware::Authenticate)
+/// middleware.
+///
+/// ```rust
+/// use turbofish::router::Role;
+///
+/// struct User {
+///   roles: Vec<Roles>,
+/// }
+///
+/// #[derive(Debug, PartialEq)]
+/// enum Roles {
+///   Admin,
+///   Editor,
+/// }
+///
+/// impl Role for Roles {
+///   type Principal = User;
+///
+///   fn granted(&self, user: &User) -> bool {
+///     user.roles.contains(self)
+///   }
+/// }
+/// ```
+pub trait Role: fmt::Debug + Send + Sync + 'static {
+  type Principal: Send + Sync + 'static;
+
+  /// Returns whether the principal has this role.
+  fn granted(&self, principal: &Self::Principal) -> bool;
+}
+
+/// An authorization requirement of a route. Requests that don't meet it
+/// are answered with `403 Forbidden` before the route's action runs.
+pub struct Guard {
+  description: String,
+  check: Box<dyn Fn(&Request) -> bool + Send + Sync>,
+}
+
+impl Guard {
+  /// Creates a guard from a policy over the principal of type `P` in the
+  /// request extensions and the route parameters. Requests without a
+  /// principal are rejected.
+  ///
+  /// ```rust
+  /// use turbofish::router::Guard;
+  ///
+  /// struct User {
+  ///   id: String,
+  /// }
+  ///
+  /// let owner = Guard::policy("owner of :id", |user: &User, params| {
+  ///   params.by_name("id") == Some(user.id.as_str())
+  /// });
+  /// ```
+  pub fn policy<P, F>(description: impl Into<String>, policy: F) -> Self
+  where
+    P: Send + Sync + 'static,
+    F: Fn(&P, &Params) -> bool + Send + Sync + 'static,
+  {
+    Self {
+      description: description.into(),
+      check: Box::new(move |req| {
+        let principal = match req.extensions().get::<P>() {
+          Some(principal) => principal,
+          None => return false,
+        };
+
+        match req.extensions().get::<Params>() {
+          Some(params) => policy(principal, params),
+          None => policy(principal, &Params::default()),
+        }
+      }),
+    }
+  }
+
+  /// Returns the description of the requirement, ex: `role Admin`.
+  pub fn description(&self) -> &str {
+    &self.description
+  }
+
+  pub(crate) fn check(&self, req: &Request) -> bool {
+    (self.check)(req)
+  }
+}
+
+impl<R: Role> From<R> for Guard {
+  fn from(role: R) -> Self {
+    Self::policy(format!("role {:?}", role), move |principal, _| {
+      role.granted(principal)
+    })
+  }
+}
+
+impl fmt::Debug for Guard {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_tuple("Guard").field(&self.description).finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::Config;
+  use crate::http::{Method, Response, StatusCode};
+  use crate::middleware::auth::{Authenticate, Credentials};
+  use crate::router::{Route, Router};
+
+  struct User {
+    name: String,
+    admin: bool,
+  }
+
+  #[derive(Debug)]
+  struct Admin;
+
+  impl Role for Admin {
+    type Principal = User;
+
+    fn granted(&self, user: &User) -> bool {
+      user.admin
+    }
+  }
+
+  #[tokio::test]
+  async fn guards() {
+    let ok = |_: Request| async { Response::new("") };
+
+    let mut router = Router::default();
+    router.route(Route::new(Method::DELETE, "/users/:name", ok).require(Admin));
+    router.route(
+      Route::new(Method::PUT, "/users/:name", ok)
+        .require(Guard::policy("self", |user: &User, params| {
+          params.by_name("name") == Some(user.name.as_str())
+        })),
+    );
+    router.middleware(Authenticate::new(|credentials| async move {
+      match credentials {
+        Credentials::Bearer(name) => Some(User {
+          admin: name == "root",
+          name,
+        }),
+        _ => None,
+      }
+    }));
+
+    let config = Config::default();
+    let send = |method: Method, path: &str, user: Option<&str>| {
+      let mut req = hyper::Request::builder().method(method).uri(path);
+      if let Some(user) = user {
+        req = req.header("authorization", format!("Bearer {}", user));
+      }
+      router.serve(req.body(hyper::Body::empty()).unwrap().into(), &config)
+    };
+
+    let status = |res: hyper::Result<Response>| res.unwrap().status();
+
+    assert_eq!(
+      status(send(Method::DELETE, "/users/ferris", None).await),
+      StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+      status(send(Method::DELETE, "/users/ferris", Some("ferris")).await),
+      StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+      status(send(Method::DELETE, "/users/ferris", Some("root")).await),
+      StatusCode::OK
+    );
+    assert_eq!(
+      status(send(Method::PUT, "/users/ferris", Some("ferris")).await),
+      StatusCode::OK
+    );
+    assert_eq!(
+      status(send(Method::PUT, "/users/ferris", Some("root")).await),
+      StatusCode::FORBIDDEN
+    );
+
+    let requirements: Vec<_> = router
+      .routes()
+      .into_iter()
+      .map(|route| {
+        let guards: Vec<_> = route.guards().iter().map(Guard::description).collect();
+        (route.method().as_str(), route.path(), guards)
+      })
+      .collect();
+
+    assert_eq!(
+      requirements,
+      vec![
+        ("DELETE", "/users/:name", vec!["role Admin"]),
+        ("PUT", "/users/:name", vec!["self"]),
+      ]
+    );
+  }
+}
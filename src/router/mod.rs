@@ -1,111 +1,311 @@
 pub(crate) mod tree;
+mod guard;
+mod path;
 
-use crate::action::BoxedAction;
-use crate::http::{Method, Request, Response, Body, StatusCode};
+use crate::action::{Action, BoxedAction};
+use crate::config::Config;
+use crate::deadline::Deadline;
+use crate::files::Files;
+use crate::http::{header, BodyGuard, BodyLimits, Extensions, Method, Request, Response, Body, StatusCode};
+use crate::middleware::auth::AuthRequirement;
+use crate::middleware::conditional::{Validator, Validators};
+use crate::middleware::rate_limit::Quota;
+use crate::middleware::{CatchPanic, Middleware, Next};
 use crate::resource::Resource;
 use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use path::clean;
 use tree::Match;
 
+pub use guard::{Guard, Role};
+pub use tree::{Param, Params};
+
 pub struct Route {
 	name: &'static str,
 	controller: &'static str,
 	method: Method,
 	handler: BoxedAction,
 	path: &'static str,
+	timeout: Option<Duration>,
+	body_limit: Option<u64>,
+	guards: Vec<Guard>,
+	extensions: Extensions,
 }
 
 impl Route {
+	pub fn new(method: Method, path: &'static str, handler: impl Action + Send + Sync + 'static) -> Self {
+		Self {
+			name: "",
+			controller: "",
+			method,
+			handler: Box::new(handler),
+			path,
+			timeout: None,
+			body_limit: None,
+			guards: Vec::new(),
+			extensions: Extensions::new(),
+		}
+	}
+
+	/// Sets the name of the route.
+	pub fn named(mut self, name: &'static str) -> Self {
+		self.name = name;
+		self
+	}
+
+	/// Sets how long the route may take to respond, overriding the
+	/// timeout set in the [`Config`].
+	pub fn timeout(mut self, timeout: Duration) -> Self {
+		self.timeout = Some(timeout);
+		self
+	}
+
+	/// Sets the maximum size of the request body in bytes, overriding the
+	/// limit set in the [`Config`].
+	pub fn body_limit(mut self, bytes: u64) -> Self {
+		self.body_limit = Some(bytes);
+		self
+	}
+
+	/// Sets the rate limit quota of the route, overriding the default quota
+	/// of the [`RateLimit`](crate::middleware::rate_limit::RateLimit) middleware.
+	pub fn rate_limit(self, quota: Quota) -> Self {
+		self.extension(quota)
+	}
+
+	/// Sets whether the route requires authentication, overriding the
+	/// default requirement of the [`Authenticate`](crate::middleware::Authenticate)
+	/// middleware.
+	pub fn auth(self, requirement: AuthRequirement) -> Self {
+		self.extension(requirement)
+	}
+
+	/// Sets how the [`Conditional`](crate::middleware::Conditional) middleware
+	/// looks up the validators of the route's resource, to evaluate
+	/// conditional writes. The lookup returns `None` if the resource doesn't
+	/// exist.
+	pub fn validators<F, Fut>(self, lookup: F) -> Self
+	where
+		F: Fn(&Request) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Option<Validators>> + Send + 'static,
+	{
+		self.extension(Validator::new(lookup))
+	}
+
+	/// Adds an authorization requirement to the route, ex: a [`Role`] or a
+	/// [`Guard::policy`]. Requests that don't meet every requirement are
+	/// answered with `403 Forbidden`.
+	pub fn require(mut self, guard: impl Into<Guard>) -> Self {
+		self.guards.push(guard.into());
+		self
+	}
+
+	/// Attaches a value to the route, for use by middleware.
+	pub fn extension<T: Send + Sync + 'static>(mut self, value: T) -> Self {
+		self.extensions.insert(value);
+		self
+	}
+
+	pub fn extensions(&self) -> &Extensions {
+		&self.extensions
+	}
+
+	/// Returns the authorization requirements of the route.
+	pub fn guards(&self) -> &[Guard] {
+		&self.guards
+	}
+
+	pub fn name(&self) -> &'static str {
+		self.name
+	}
+
+	pub fn controller(&self) -> &'static str {
+		self.controller
+	}
+
+	pub fn method(&self) -> &Method {
+		&self.method
+	}
+
+	/// Returns the path pattern the route was registered with, ex: `/users/:id`.
+	pub fn path(&self) -> &'static str {
+		self.path
+	}
+
 	pub async fn call(&self, req: Request) -> Response {
 		self.handler.call(req).await
 	}
 }
 
 pub struct Router {
-	routes: HashMap<Method, tree::Node<Route>>,
+	routes: HashMap<Method, tree::Node<Arc<Route>>>,
+	middleware: Vec<Box<dyn Middleware>>,
+	catch_panic: CatchPanic,
 }
 
 impl Default for Router {
 	fn default() -> Self {
 		Self {
 			routes: HashMap::with_capacity(5),
+			middleware: Vec::new(),
+			catch_panic: CatchPanic::default(),
 		}
 	}
 }
 
 impl Router {
-	pub async fn serve(&self, req: Request) -> hyper::Result<Response> {
+	/// Serves a request, running it through the middleware stack before
+	/// dispatching it to the matching route.
+	pub async fn serve(&self, mut req: Request, config: &Config) -> hyper::Result<Response> {
+		let mut limits = BodyLimits::new(config);
+
+		if let Ok(lookup) = self.lookup(req.method(), req.path()) {
+			let route = lookup.value.clone();
+			if let Some(timeout) = route.timeout.or(config.timeout) {
+				req.extensions_mut().insert(Deadline::after(timeout));
+			}
+			if let Some(body_limit) = route.body_limit {
+				limits.max_size = Some(body_limit);
+			}
+			req.extensions_mut().insert(lookup.params);
+			req.extensions_mut().insert(route);
+		}
+
+		let (body, guard) = limits.apply(req.take_body());
+		*req.body_mut() = body;
+		req.extensions_mut().insert(guard);
+
+		let cookies = req.cookies().clone();
+		cookies.set_key(config.secret_key.clone());
+
+		let mut res = self.catch_panic.run(req, Next::new(self, &self.middleware)).await;
+		for cookie in cookies.delta() {
+			res.headers_mut().append(header::SET_COOKIE, cookie);
+		}
+
+		Ok(res)
+	}
+
+	/// Calls the route matched by `serve`, falling back to redirects,
+	/// `OPTIONS` and `405 Method Not Allowed` responses.
+	pub(crate) async fn dispatch(&self, req: Request) -> Response {
+		if let Some(route) = req.extensions().get::<Arc<Route>>().cloned() {
+			let guard = req.extensions().get::<BodyGuard>().cloned();
+			if let Some(Err(err)) = guard.as_ref().map(|guard| guard.check(req.headers())) {
+				return status(err.status());
+			}
+
+			if !route.guards.iter().all(|guard| guard.check(&req)) {
+				return status(StatusCode::FORBIDDEN);
+			}
+
+			let res = match req.extensions().get::<Deadline>().copied() {
+				Some(deadline) => match deadline.run(route.call(req)).await {
+					Ok(res) => res,
+					Err(_) => status(StatusCode::SERVICE_UNAVAILABLE),
+				},
+				None => route.call(req).await,
+			};
+
+			// the handler may have ignored a body error
+			return match guard.and_then(|guard| guard.violation()) {
+				Some(err) => status(err.status()),
+				None => res,
+			};
+		}
+
 		let root = self.routes.get(req.method());
-		let path = req.uri().path();
+		let path = req.path();
 		if let Some(root) = root {
-			match root.match_path(path) {
-				Ok(lookup) => {
-					req.extensions_mut().insert(lookup.params);
-					return Ok(lookup.value.call(req).await);
-				}
-				Err(tsr) => {
-					if req.method() != Method::CONNECT && path != "/" {
-						let code = match *req.method() {
-							// Moved Permanently, request with GET method
-							Method::GET => StatusCode::MOVED_PERMANENTLY,
-							// Permanent Redirect, request with same method
-							_ => StatusCode::PERMANENT_REDIRECT,
-						};
+			if let Err(tsr) = root.match_path(path) {
+				if req.method() != Method::CONNECT && path != "/" {
+					let code = match *req.method() {
+						// Moved Permanently, request with GET method
+						Method::GET => StatusCode::MOVED_PERMANENTLY,
+						// Permanent Redirect, request with same method
+						_ => StatusCode::PERMANENT_REDIRECT,
+					};
 
-						if tsr {
-							let path = if path.len() > 1 && path.ends_with('/') {
-								path[..path.len() - 1].to_string()
-							} else {
-								path.to_string() + "/"
-							};
-
-							return Ok(
-								Response::builder()
-									.header(header::LOCATION, path.as_str())
-									.status(code)
-									.body(Body::empty())
-									.unwrap(),
-							);
+					if tsr {
+						let path = if path.len() > 1 && path.ends_with('/') {
+							path[..path.len() - 1].to_string()
+						} else {
+							path.to_string() + "/"
 						};
 
-						if let Some(fixed_path) = root.find_case_insensitive_path(&clean(path), true) {
-							return Ok(
-								Response::builder()
-									.header(header::LOCATION, fixed_path.as_str())
-									.status(code)
-									.body(Body::empty())
-									.unwrap(),
-							);
-						}
+						return Response::builder()
+							.header(header::LOCATION, path.as_str())
+							.status(code)
+							.body(Body::empty())
+							.unwrap();
 					};
-				}
+
+					if let Some(fixed_path) = root.find_case_insensitive_path(&clean(path), true) {
+						return Response::builder()
+							.header(header::LOCATION, fixed_path.as_str())
+							.status(code)
+							.body(Body::empty())
+							.unwrap();
+					}
+				};
 			}
 		};
 
 		if req.method() == Method::OPTIONS {
 			let allow = self.allowed(path).join(", ");
-			if allow != "" {
-				return Ok(
-					Response::builder()
-						.header(header::ALLOW, allow)
-						.body(Body::empty())
-						.unwrap(),
-				);
+			if !allow.is_empty() {
+				return Response::builder()
+					.header(header::ALLOW, allow)
+					.body(Body::empty())
+					.unwrap();
 			}
 		} else {
 			let allow = self.allowed(path).join(", ");
 
 			if !allow.is_empty() {
-				return Ok(
-					Response::builder()
-						.header(header::ALLOW, allow)
-						.status(StatusCode::METHOD_NOT_ALLOWED)
-						.body(Body::empty())
-						.unwrap(),
-				);
+				return Response::builder()
+					.header(header::ALLOW, allow)
+					.status(StatusCode::METHOD_NOT_ALLOWED)
+					.body(Body::empty())
+					.unwrap();
 			}
 		};
 
-		Ok(Response::builder().status(404).body(Body::empty()).unwrap())
+		Response::builder().status(404).body(Body::empty()).unwrap()
+	}
+
+	/// Returns the methods that have a route matching `path`, plus `OPTIONS`
+	/// if there are any. The path `*` matches every registered method.
+	pub fn allowed(&self, path: &str) -> Vec<&str> {
+		let mut allowed = self
+			.routes
+			.iter()
+			.filter(|(method, _)| **method != Method::OPTIONS)
+			.filter(|(_, root)| path == "*" || root.match_path(path).is_ok())
+			.map(|(method, _)| method.as_str())
+			.collect::<Vec<_>>();
+
+		if !allowed.is_empty() {
+			allowed.push("OPTIONS");
+			allowed.sort_unstable();
+		}
+
+		allowed
+	}
+
+	/// Adds a middleware to the stack. Middleware run in the order they
+	/// are added, wrapping every request served by the router.
+	pub fn middleware(&mut self, middleware: impl Middleware + 'static) {
+		self.middleware.push(Box::new(middleware));
+	}
+
+	/// Sets how panics in middleware and routes are answered and reported
+	/// (default is [`CatchPanic::new`]).
+	pub fn catch_panic(&mut self, catch_panic: CatchPanic) {
+		self.catch_panic = catch_panic;
 	}
 
 	pub fn resource(&mut self, resource: impl Resource) {
@@ -114,7 +314,30 @@ impl Router {
 		}
 	}
 
-	pub fn node(&self, method: &Method) -> Option<&tree::Node<Route>> {
+	/// Serves the files in the `root` directory at `path`, which must end
+	/// with a catch-all parameter, ex: `/assets/*path`. See [`Files`] for
+	/// more options.
+	pub fn files(&mut self, path: &'static str, root: impl Into<PathBuf>) {
+		let files = Files::new(root);
+		self.route(Route::new(Method::GET, path, files.clone()));
+		self.route(Route::new(Method::HEAD, path, files));
+	}
+
+	/// Returns every route, sorted by path and method, ex: to review the
+	/// authorization requirements of each route.
+	pub fn routes(&self) -> Vec<&Route> {
+		let mut routes = self
+			.routes
+			.values()
+			.flat_map(|root| root.values())
+			.map(|route| &**route)
+			.collect::<Vec<_>>();
+
+		routes.sort_by(|a, b| (a.path, a.method.as_str()).cmp(&(b.path, b.method.as_str())));
+		routes
+	}
+
+	pub fn node(&self, method: &Method) -> Option<&tree::Node<Arc<Route>>> {
 		self.routes.get(method)
 	}
 
@@ -122,14 +345,18 @@ impl Router {
 		self
 			.routes
 			.entry(route.method.clone())
-			.or_insert_with(tree::Node::default)
-			.insert(route.path, route);
+			.or_default()
+			.insert(route.path, Arc::new(route));
 	}
 
-	pub fn lookup(&self, method: &Method, path: &str) -> Result<Match<Route>, bool> {
+	pub fn lookup(&self, method: &Method, path: &str) -> Result<Match<'_, Arc<Route>>, bool> {
 		self
 			.routes
 			.get(method)
 			.map_or(Err(false), |r| r.match_path(path))
 	}
 }
+
+fn status(status: StatusCode) -> Response {
+	Response::builder().status(status).body(Body::empty()).unwrap()
+}
@@ -0,0 +1,126 @@
+/// Returns the canonical URL path for `p`, eliminating `.` and `..` elements.
+///
+/// The following rules are applied iteratively until no further processing
+/// can be done:
+///
+/// 1. Replace multiple slashes with a single slash.
+/// 2. Eliminate each `.` path name element (the current directory).
+/// 3. Eliminate each inner `..` path name element (the parent directory)
+///    along with the non-`..` element that precedes it.
+/// 4. Eliminate `..` elements that begin a rooted path, that is, replace
+///    `/..` by `/` at the beginning of a path.
+///
+/// If the result of this process is an empty string, `/` is returned.
+pub fn clean(p: &str) -> String {
+  if p.is_empty() {
+    return "/".to_string();
+  }
+
+  let p = p.as_bytes();
+  let n = p.len();
+  let mut buf = Vec::with_capacity(n + 1);
+  buf.push(b'/');
+
+  // read index, the next byte to process. path must start with '/'
+  let mut r = if p[0] == b'/' { 1 } else { 0 };
+  let mut trailing = n > 1 && p[n - 1] == b'/';
+
+  while r < n {
+    if p[r] == b'/' {
+      // empty path element, trailing slash is added after the end
+      r += 1;
+    } else if p[r] == b'.' && r + 1 == n {
+      trailing = true;
+      r += 1;
+    } else if p[r] == b'.' && p[r + 1] == b'/' {
+      // . element
+      r += 2;
+    } else if p[r] == b'.' && p[r + 1] == b'.' && (r + 2 == n || p[r + 2] == b'/') {
+      // .. element: remove to last /
+      r += 3;
+
+      if buf.len() > 1 {
+        // can backtrack
+        while buf.len() > 1 && buf.pop() != Some(b'/') {}
+      }
+    } else {
+      // real path element. add slash if needed
+      if buf.len() > 1 {
+        buf.push(b'/');
+      }
+
+      // copy element
+      while r < n && p[r] != b'/' {
+        buf.push(p[r]);
+        r += 1;
+      }
+    }
+  }
+
+  // re-append trailing slash
+  if trailing && buf.len() > 1 {
+    buf.push(b'/');
+  }
+
+  String::from_utf8(buf).expect("cleaned path is not valid utf-8")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_path_clean() {
+    let tests = vec![
+      // already clean
+      ("/", "/"),
+      ("/abc", "/abc"),
+      ("/a/b/c", "/a/b/c"),
+      ("/abc/", "/abc/"),
+      ("/a/b/c/", "/a/b/c/"),
+      // missing root
+      ("", "/"),
+      ("a/", "/a/"),
+      ("abc", "/abc"),
+      ("abc/def", "/abc/def"),
+      ("a/b/c", "/a/b/c"),
+      // remove doubled slash
+      ("//", "/"),
+      ("/abc//", "/abc/"),
+      ("/abc/def//", "/abc/def/"),
+      ("/a/b/c//", "/a/b/c/"),
+      ("/abc//def//ghi", "/abc/def/ghi"),
+      ("//abc", "/abc"),
+      ("///abc", "/abc"),
+      ("//abc//", "/abc/"),
+      // remove . elements
+      (".", "/"),
+      ("./", "/"),
+      ("/abc/./def", "/abc/def"),
+      ("/./abc/def", "/abc/def"),
+      ("/abc/.", "/abc/"),
+      // remove .. elements
+      ("..", "/"),
+      ("../", "/"),
+      ("../../", "/"),
+      ("../..", "/"),
+      ("../../abc", "/abc"),
+      ("/abc/def/ghi/../jkl", "/abc/def/jkl"),
+      ("/abc/def/../ghi/../jkl", "/abc/jkl"),
+      ("/abc/def/..", "/abc"),
+      ("/abc/def/../..", "/"),
+      ("/abc/def/../../..", "/"),
+      ("/abc/def/../../..", "/"),
+      ("/abc/def/../../../ghi/jkl/../../../mno", "/mno"),
+      // combinations
+      ("abc/./../def", "/def"),
+      ("abc//./../def", "/def"),
+      ("abc/../../././../def", "/def"),
+    ];
+
+    for (path, expected) in tests {
+      assert_eq!(clean(path), expected, "clean({:?})", path);
+      assert_eq!(clean(expected), expected, "clean({:?})", expected);
+    }
+  }
+}
@@ -15,7 +15,7 @@ pub struct Param {
 }
 
 impl Param {
-  fn new(key: &str, value: &str) -> Self {
+  pub fn new(key: &str, value: &str) -> Self {
     Self {
       key: key.to_string(),
       value: value.to_string(),
@@ -23,15 +23,9 @@ impl Param {
   }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Default, PartialEq)]
 pub struct Params(pub Vec<Param>);
 
-impl Default for Params {
-  fn default() -> Self {
-    Self(Vec::new())
-  }
-}
-
 impl Index<usize> for Params {
   type Output = Param;
 
@@ -48,14 +42,14 @@ impl std::ops::IndexMut<usize> for Params {
 }
 
 impl Params {
-  fn by_name(&self, name: &str) -> Option<&str> {
+  pub fn by_name(&self, name: &str) -> Option<&str> {
     match self.0.iter().find(|param| param.key == name) {
       Some(param) => Some(&param.value),
       None => None,
     }
   }
 
-  fn is_empty(&self) -> bool {
+  pub fn is_empty(&self) -> bool {
     self.0.is_empty()
   }
 
@@ -130,6 +124,15 @@ impl<V> Node<V> {
     new_pos
   }
 
+  /// Returns the values of the node and all of its children.
+  pub fn values(&self) -> Vec<&V> {
+    self
+      .value
+      .iter()
+      .chain(self.children.iter().flat_map(|child| child.values()))
+      .collect()
+  }
+
   /// Insert a `Node` with the given value to the path.
   pub fn insert(&mut self, path: &str, value: V) {
     let full_path = <&str>::clone(&path);
@@ -226,7 +229,7 @@ impl<V> Node<V> {
 
     // Check if the wildcard matches
     if path.len() >= self.path.len()
-      && self.path == &path[..self.path.len()]
+      && self.path == path[..self.path.len()]
       // Adding a child to a CatchAll Node is not possible
       && self.node_type != NodeType::CatchAll
       // Check for longer wildcard, e.g. :name and :names
@@ -238,7 +241,7 @@ impl<V> Node<V> {
       let path_seg = if self.node_type == NodeType::CatchAll {
         str::from_utf8(path).unwrap()
       } else {
-        str::from_utf8(path).unwrap().splitn(2, '/').next().unwrap()
+        str::from_utf8(path).unwrap().split('/').next().unwrap()
       };
 
       let prefix = format!(
@@ -376,16 +379,16 @@ impl<V> Node<V> {
     self.children[0].children = vec![Box::new(child)];
   }
 
-  pub fn match_path(&self, path: &str) -> Result<Match<V>, bool> {
+  pub fn match_path(&self, path: &str) -> Result<Match<'_, V>, bool> {
     self.match_helper(path.as_ref(), Params::default())
   }
 
   // outer loop for walking the tree to get a path's value
   #[inline]
-  fn match_helper<'a>(&'a self, mut path: &[u8], params: Params) -> Result<Match<V>, bool> {
+  fn match_helper(&self, mut path: &[u8], params: Params) -> Result<Match<'_, V>, bool> {
     let prefix = self.path.clone();
     if path.len() > prefix.len() {
-      if prefix == &path[..prefix.len()] {
+      if prefix == path[..prefix.len()] {
         path = &path[prefix.len()..];
 
         // If this node does not have a wildcard (Param or CatchAll)
@@ -448,11 +451,11 @@ impl<V> Node<V> {
 
   // helper function for handling a wildcard child used by `match`
   #[inline]
-  fn handle_wild_child<'a>(
-    &'a self,
+  fn handle_wild_child(
+    &self,
     mut path: &[u8],
     mut params: Params,
-  ) -> Result<Match<V>, bool> {
+  ) -> Result<Match<'_, V>, bool> {
     match self.node_type {
       NodeType::Param => {
         // find param end (either '/' or path end)
@@ -1239,7 +1242,7 @@ mod tests {
       });
 
       if recv.is_ok() {
-        panic!(panic_msg);
+        panic!("{}", panic_msg);
       }
     }
   }
@@ -1504,7 +1507,7 @@ mod tests {
       inn: &'static str,
       out: &'static str,
       slash: bool,
-    };
+    }
 
     let tests: Vec<Test> = tests
       .into_iter()
@@ -1518,14 +1521,11 @@ mod tests {
     // With fixTrailingSlash = true
     for test in &tests {
       let res = tree.find_case_insensitive_path(test.inn, true);
-      match res {
-        None => (),
-        Some(res) => {
-          if res != test.out {
-            panic!("Wrong result for route '{}': {}", res, test.out);
-          }
+      if let Some(res) = res {
+        if res != test.out {
+          panic!("Wrong result for route '{}': {}", res, test.out);
         }
-      };
+      }
     }
 
     // With fixTrailingSlash = false
@@ -1,54 +1,115 @@
-use crate::http::{Request, Response};
+use crate::http::{Body, Request};
 use crate::turbofish::Turbofish;
+use hyper::server::conn::AddrStream;
 use hyper::service::Service;
+use std::convert::Infallible;
 use std::future::Future;
+use std::net::SocketAddr;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::task::{Context, Poll};
 
 impl Turbofish {
-  async fn serve(self: Arc<Self>, req: Request) -> hyper::Result<Response> {
-      self.router.serve(req).await
+  async fn serve(self: Arc<Self>, req: Request) -> hyper::Response<Body> {
+    self.router.serve(req, &self.config).await.unwrap().into()
   }
 }
 
-pub(crate) struct MakeTurbofishService(TurbofishService);
+/// Connection statistics of a running server.
+///
+/// A handle is inserted into the extensions of every request the server
+/// receives, so middleware can report on it.
+#[derive(Clone, Default)]
+pub struct Connections(Arc<Counts>);
+
+#[derive(Default)]
+struct Counts {
+  open: AtomicU64,
+  total: AtomicU64,
+}
+
+impl Connections {
+  /// Returns the number of currently open connections.
+  pub fn open(&self) -> u64 {
+    self.0.open.load(Ordering::Relaxed)
+  }
+
+  /// Returns the number of connections accepted since the server started.
+  pub fn total(&self) -> u64 {
+    self.0.total.load(Ordering::Relaxed)
+  }
+
+  fn accept(&self) -> Arc<Connection> {
+    self.0.open.fetch_add(1, Ordering::Relaxed);
+    self.0.total.fetch_add(1, Ordering::Relaxed);
+    Arc::new(Connection(self.clone()))
+  }
+}
+
+/// An open connection, closed when hyper drops its service.
+struct Connection(Connections);
+
+impl Drop for Connection {
+  fn drop(&mut self) {
+    (self.0).0.open.fetch_sub(1, Ordering::Relaxed);
+  }
+}
+
+pub(crate) struct MakeTurbofishService {
+  turbofish: Arc<Turbofish>,
+  connections: Connections,
+}
 
 impl MakeTurbofishService {
   pub fn new(t: Turbofish) -> Self {
-    Self(TurbofishService(Arc::new(t)))
+    Self {
+      turbofish: Arc::new(t),
+      connections: Connections::default(),
+    }
   }
 }
 
-impl<T> Service<T> for MakeTurbofishService {
+impl<'a> Service<&'a AddrStream> for MakeTurbofishService {
   type Response = TurbofishService;
-  type Error = hyper::Error;
+  type Error = Infallible;
   type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
 
   fn poll_ready(&mut self, _: &mut Context) -> Poll<Result<(), Self::Error>> {
     Poll::Ready(Ok(()))
   }
 
-  fn call(&mut self, _: T) -> Self::Future {
-    let service = self.0.clone();
-    let fut = async move { Ok(service) };
-    Box::pin(fut)
+  fn call(&mut self, conn: &'a AddrStream) -> Self::Future {
+    let service = TurbofishService {
+      turbofish: self.turbofish.clone(),
+      remote_addr: conn.remote_addr(),
+      connection: self.connections.accept(),
+    };
+    Box::pin(async move { Ok(service) })
   }
 }
 
 #[derive(Clone)]
-pub(crate) struct TurbofishService(Arc<Turbofish>);
+pub(crate) struct TurbofishService {
+  turbofish: Arc<Turbofish>,
+  remote_addr: SocketAddr,
+  connection: Arc<Connection>,
+}
 
-impl Service<Request> for TurbofishService {
-  type Response = Response;
-  type Error = hyper::Error;
-  type Future = Pin<Box<dyn Future<Output = hyper::Result<Response>> + Send>>;
+impl Service<hyper::Request<hyper::Body>> for TurbofishService {
+  type Response = hyper::Response<Body>;
+  type Error = Infallible;
+  type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
 
   fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
     Poll::Ready(Ok(()))
   }
 
-  fn call(&mut self, req: Request) -> Self::Future {
-      Box::pin(self.0.clone().serve(req))
+  fn call(&mut self, req: hyper::Request<hyper::Body>) -> Self::Future {
+    let mut req = Request::from(req);
+    req.set_remote_addr(self.remote_addr);
+    req.extensions_mut().insert(self.connection.0.clone());
+    let turbofish = self.turbofish.clone();
+    Box::pin(async move { Ok(turbofish.serve(req).await) })
   }
 }
@@ -4,17 +4,15 @@ use crate::server::MakeTurbofishService;
 use std::net::ToSocketAddrs;
 use std::time::Duration;
 
+#[derive(Default)]
 pub struct Turbofish {
-  config: Config,
+  pub(crate) config: Config,
   pub(crate) router: Router
 }
 
 impl Turbofish {
   pub fn new() -> Self {
-    Self {
-      config: Config::default(),
-      router: Router::default(),
-    }
+    Self::default()
   }
 
   pub fn config(mut self, config: Config) -> Self {
@@ -41,7 +39,7 @@ mod tests {
 
   #[test]
   fn api() {
-    Turbofish::new()
+    let _server = Turbofish::new()
       .config(Config::builder().keep_alive(100).port(3000))
       .swim();
   }
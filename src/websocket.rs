@@ -0,0 +1,428 @@
+//! WebSocket upgrades.
+//!
+//! A route accepts WebSocket connections by validating the handshake with
+//! [`WebSocketUpgrade::new`] and answering with the response returned by
+//! [`on_upgrade`](WebSocketUpgrade::on_upgrade). The upgrade request goes
+//! through the same router, middleware and guards as any other request.
+//!
+//! ```rust
+//! use futures::{SinkExt, StreamExt};
+//! use turbofish::http::{Request, Response};
+//! use turbofish::websocket::{Message, WebSocketUpgrade};
+//!
+//! async fn echo(mut req: Request) -> Response {
+//!   let upgrade = match WebSocketUpgrade::new(&mut req) {
+//!     Ok(upgrade) => upgrade,
+//!     Err(err) => return err.into(),
+//!   };
+//!
+//!   upgrade.protocols(["echo"]).on_upgrade(|mut socket| async move {
+//!     while let Some(Ok(message)) = socket.next().await {
+//!       if let Message::Text(_) | Message::Binary(_) = message {
+//!         if socket.send(message).await.is_err() {
+//!           break;
+//!         }
+//!       }
+//!     }
+//!   })
+//! }
+//! ```
+//!
+//! Pings are answered automatically. The `permessage-deflate` extension is
+//! not negotiated, so messages are always sent uncompressed.
+
+use crate::http::{
+  header, Body, HeaderMap, HeaderValue, Method, Request, Response, StatusCode, Version,
+};
+use futures::{Sink, Stream};
+use hyper::upgrade::{OnUpgrade, Upgraded};
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::{Role, WebSocketConfig};
+use tokio_tungstenite::WebSocketStream;
+
+#[doc(no_inline)]
+pub use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+#[doc(no_inline)]
+pub use tokio_tungstenite::tungstenite::protocol::{CloseFrame, Message};
+#[doc(no_inline)]
+pub use tokio_tungstenite::tungstenite::Error as WebSocketError;
+
+/// A validated WebSocket handshake, waiting to be accepted.
+pub struct WebSocketUpgrade {
+  key: HeaderValue,
+  offered: Vec<String>,
+  protocol: Option<String>,
+  config: WebSocketConfig,
+  on_upgrade: OnUpgrade,
+}
+
+impl WebSocketUpgrade {
+  /// Validates the WebSocket handshake of `req`.
+  pub fn new(req: &mut Request) -> Result<Self, UpgradeError> {
+    if *req.method() != Method::GET {
+      return Err(UpgradeError::MethodNotAllowed);
+    }
+
+    if req.version() != Version::HTTP_11 {
+      return Err(UpgradeError::UnsupportedHttpVersion);
+    }
+
+    let headers = req.headers();
+    if !has_token(headers, header::CONNECTION, "upgrade")
+      || !has_token(headers, header::UPGRADE, "websocket")
+    {
+      return Err(UpgradeError::NotWebSocket);
+    }
+
+    if headers
+      .get(header::SEC_WEBSOCKET_VERSION)
+      .map(HeaderValue::as_bytes)
+      != Some(b"13")
+    {
+      return Err(UpgradeError::UnsupportedVersion);
+    }
+
+    let key = match headers.get(header::SEC_WEBSOCKET_KEY) {
+      Some(key) if base64::decode(key.as_bytes()).is_ok_and(|key| key.len() == 16) => key.clone(),
+      _ => return Err(UpgradeError::InvalidKey),
+    };
+
+    let offered = tokens(headers, header::SEC_WEBSOCKET_PROTOCOL)
+      .map(str::to_owned)
+      .collect();
+
+    let on_upgrade = req
+      .extensions_mut()
+      .remove::<OnUpgrade>()
+      .ok_or(UpgradeError::NotUpgradable)?;
+
+    Ok(Self {
+      key,
+      offered,
+      protocol: None,
+      config: WebSocketConfig::default(),
+      on_upgrade,
+    })
+  }
+
+  /// Selects the first of `protocols`, in order of preference, that the
+  /// client offered in `Sec-WebSocket-Protocol`. If none match, no
+  /// subprotocol is selected and the client decides whether to continue.
+  pub fn protocols<I>(mut self, protocols: I) -> Self
+  where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+  {
+    self.protocol = protocols
+      .into_iter()
+      .find(|protocol| {
+        self
+          .offered
+          .iter()
+          .any(|offered| offered == protocol.as_ref())
+      })
+      .map(|protocol| protocol.as_ref().to_owned());
+    self
+  }
+
+  /// Returns the subprotocol selected with
+  /// [`protocols`](WebSocketUpgrade::protocols).
+  pub fn protocol(&self) -> Option<&str> {
+    self.protocol.as_deref()
+  }
+
+  /// Sets the maximum size of a single frame in bytes (default is 16 MiB).
+  pub fn max_frame_size(mut self, bytes: impl Into<Option<usize>>) -> Self {
+    self.config.max_frame_size = bytes.into();
+    self
+  }
+
+  /// Sets the maximum size of a message in bytes (default is 64 MiB).
+  pub fn max_message_size(mut self, bytes: impl Into<Option<usize>>) -> Self {
+    self.config.max_message_size = bytes.into();
+    self
+  }
+
+  /// Accepts the handshake, returning the `101 Switching Protocols`
+  /// response. Once it has been sent, `callback` is spawned with the
+  /// connected socket.
+  pub fn on_upgrade<F, Fut>(self, callback: F) -> Response
+  where
+    F: FnOnce(WebSocket) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+  {
+    let accept = derive_accept_key(self.key.as_bytes());
+    let protocol = self.protocol.clone();
+    let config = self.config;
+    let on_upgrade = self.on_upgrade;
+
+    tokio::spawn(async move {
+      let upgraded = match on_upgrade.await {
+        Ok(upgraded) => upgraded,
+        Err(err) => {
+          log::debug!("websocket upgrade failed: {}", err);
+          return;
+        }
+      };
+
+      let inner = WebSocketStream::from_raw_socket(upgraded, Role::Server, Some(config)).await;
+      callback(WebSocket { inner, protocol }).await;
+    });
+
+    // tungstenite 0.20 has no support for extensions, so
+    // `Sec-WebSocket-Extensions` is ignored and permessage-deflate is never
+    // negotiated
+    let mut res = Response::builder()
+      .status(StatusCode::SWITCHING_PROTOCOLS)
+      .header(header::CONNECTION, "upgrade")
+      .header(header::UPGRADE, "websocket")
+      .header(header::SEC_WEBSOCKET_ACCEPT, accept);
+
+    if let Some(protocol) = &self.protocol {
+      res = res.header(header::SEC_WEBSOCKET_PROTOCOL, protocol.as_str());
+    }
+
+    res.body(Body::empty()).unwrap()
+  }
+}
+
+fn tokens(headers: &HeaderMap, name: header::HeaderName) -> impl Iterator<Item = &str> {
+  headers
+    .get_all(name)
+    .into_iter()
+    .filter_map(|value| value.to_str().ok())
+    .flat_map(|value| value.split(','))
+    .map(str::trim)
+}
+
+fn has_token(headers: &HeaderMap, name: header::HeaderName, token: &str) -> bool {
+  tokens(headers, name).any(|value| value.eq_ignore_ascii_case(token))
+}
+
+/// A connected WebSocket.
+///
+/// Messages are received through the [`Stream`] implementation and sent
+/// through the [`Sink`] implementation.
+pub struct WebSocket {
+  inner: WebSocketStream<Upgraded>,
+  protocol: Option<String>,
+}
+
+impl WebSocket {
+  /// Returns the negotiated subprotocol.
+  pub fn protocol(&self) -> Option<&str> {
+    self.protocol.as_deref()
+  }
+
+  /// Closes the connection with a close code and reason.
+  pub async fn close(mut self, code: CloseCode, reason: &str) -> Result<(), WebSocketError> {
+    let frame = CloseFrame {
+      code,
+      reason: reason.to_owned().into(),
+    };
+    self.inner.close(Some(frame)).await
+  }
+}
+
+impl Stream for WebSocket {
+  type Item = Result<Message, WebSocketError>;
+
+  fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    Pin::new(&mut self.inner).poll_next(cx)
+  }
+}
+
+impl Sink<Message> for WebSocket {
+  type Error = WebSocketError;
+
+  fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    Pin::new(&mut self.inner).poll_ready(cx)
+  }
+
+  fn start_send(mut self: Pin<&mut Self>, item: Message) -> Result<(), Self::Error> {
+    Pin::new(&mut self.inner).start_send(item)
+  }
+
+  fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    Pin::new(&mut self.inner).poll_flush(cx)
+  }
+
+  fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    Pin::new(&mut self.inner).poll_close(cx)
+  }
+}
+
+/// An error returned when a request is not a valid WebSocket handshake.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpgradeError {
+  /// The request method is not `GET`.
+  MethodNotAllowed,
+  /// The request is not an HTTP/1.1 request.
+  UnsupportedHttpVersion,
+  /// The request doesn't ask to upgrade to a WebSocket.
+  NotWebSocket,
+  /// The `Sec-WebSocket-Version` is not 13.
+  UnsupportedVersion,
+  /// The `Sec-WebSocket-Key` is missing or invalid.
+  InvalidKey,
+  /// The connection can't be upgraded, for example because the request
+  /// was not received from a server connection.
+  NotUpgradable,
+}
+
+impl UpgradeError {
+  /// Returns the status code the request should be answered with.
+  pub fn status(&self) -> StatusCode {
+    match self {
+      UpgradeError::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+      UpgradeError::UnsupportedVersion => StatusCode::UPGRADE_REQUIRED,
+      UpgradeError::NotUpgradable => StatusCode::INTERNAL_SERVER_ERROR,
+      _ => StatusCode::BAD_REQUEST,
+    }
+  }
+}
+
+impl From<UpgradeError> for Response {
+  fn from(err: UpgradeError) -> Self {
+    let mut res = Response::builder().status(err.status());
+    if err == UpgradeError::UnsupportedVersion {
+      res = res.header(header::SEC_WEBSOCKET_VERSION, "13");
+    }
+    res.body(Body::empty()).unwrap()
+  }
+}
+
+impl fmt::Display for UpgradeError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      UpgradeError::MethodNotAllowed => f.write_str("websocket handshake must use GET"),
+      UpgradeError::UnsupportedHttpVersion => f.write_str("websocket handshake must use HTTP/1.1"),
+      UpgradeError::NotWebSocket => f.write_str("request is not a websocket upgrade"),
+      UpgradeError::UnsupportedVersion => f.write_str("unsupported websocket version"),
+      UpgradeError::InvalidKey => f.write_str("invalid websocket key"),
+      UpgradeError::NotUpgradable => f.write_str("connection cannot be upgraded"),
+    }
+  }
+}
+
+impl Error for UpgradeError {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::router::{Route, Router};
+  use crate::server::MakeTurbofishService;
+  use crate::turbofish::Turbofish;
+  use futures::{SinkExt, StreamExt};
+  use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+  async fn echo(mut req: Request) -> Response {
+    let upgrade = match WebSocketUpgrade::new(&mut req) {
+      Ok(upgrade) => upgrade,
+      Err(err) => return err.into(),
+    };
+
+    upgrade
+      .protocols(["v2", "v1"])
+      .on_upgrade(|mut socket| async move {
+        assert_eq!(socket.protocol(), Some("v1"));
+        while let Some(Ok(message)) = socket.next().await {
+          if let Message::Text(text) = message {
+            if text == "bye" {
+              socket.close(CloseCode::Away, "bye").await.unwrap();
+              return;
+            }
+            socket.send(Message::Text(text)).await.unwrap();
+          }
+        }
+      })
+  }
+
+  fn serve(router: Router) -> std::net::SocketAddr {
+    let mut turbofish = Turbofish::new();
+    turbofish.router = router;
+
+    let server =
+      hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(MakeTurbofishService::new(turbofish));
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    addr
+  }
+
+  #[tokio::test]
+  async fn websocket() {
+    let mut router = Router::default();
+    router.route(Route::new(Method::GET, "/ws", echo));
+    let addr = serve(router);
+
+    let mut req = format!("ws://{}/ws", addr).into_client_request().unwrap();
+    req
+      .headers_mut()
+      .insert("sec-websocket-protocol", "v0, v1".parse().unwrap());
+    let (mut socket, res) = tokio_tungstenite::connect_async(req).await.unwrap();
+    assert_eq!(res.headers()["sec-websocket-protocol"], "v1");
+
+    socket.send(Message::Text("hello".into())).await.unwrap();
+    assert_eq!(
+      socket.next().await.unwrap().unwrap(),
+      Message::Text("hello".into())
+    );
+
+    socket.send(Message::Ping(b"ping".to_vec())).await.unwrap();
+    assert_eq!(
+      socket.next().await.unwrap().unwrap(),
+      Message::Pong(b"ping".to_vec())
+    );
+
+    socket.send(Message::Text("bye".into())).await.unwrap();
+    match socket.next().await.unwrap().unwrap() {
+      Message::Close(Some(frame)) => assert_eq!(frame.code, CloseCode::Away),
+      other => panic!("expected close, got {:?}", other),
+    }
+
+    let mut req: Request = hyper::Request::get("/ws")
+      .header("connection", "upgrade")
+      .header("upgrade", "websocket")
+      .header("sec-websocket-version", "8")
+      .body(hyper::Body::empty())
+      .unwrap()
+      .into();
+    let err = WebSocketUpgrade::new(&mut req).err().unwrap();
+    let res = Response::from(err);
+    assert_eq!(res.status(), StatusCode::UPGRADE_REQUIRED);
+    assert_eq!(res.headers()[header::SEC_WEBSOCKET_VERSION], "13");
+  }
+
+  #[tokio::test]
+  async fn max_frame_size() {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let tx = std::sync::Mutex::new(Some(tx));
+
+    let mut router = Router::default();
+    router.route(Route::new(Method::GET, "/ws", move |mut req: Request| {
+      let tx = tx.lock().unwrap().take().unwrap();
+      async move {
+        let upgrade = WebSocketUpgrade::new(&mut req).unwrap().max_frame_size(16);
+        upgrade.on_upgrade(|mut socket| async move {
+          let _ = tx.send(socket.next().await.unwrap());
+        })
+      }
+    }));
+    let addr = serve(router);
+
+    let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr))
+      .await
+      .unwrap();
+    socket.send(Message::Text("x".repeat(32))).await.unwrap();
+
+    match rx.await.unwrap() {
+      Err(WebSocketError::Capacity(_)) => {}
+      other => panic!("expected a capacity error, got {:?}", other),
+    }
+  }
+}
@@ -15,9 +15,11 @@ pub struct ColumnBuilder {
   // foreign-key constraint
   foreign: Option<ForeignKeyBuilder>,
   // column checks
-  check: Option<Box<dyn Fn(&mut BaseBuilder)>>,
+  check: Option<Check>,
 }
 
+type Check = Box<dyn Fn(&mut BaseBuilder)>;
+
 impl ChildBuilder for ColumnBuilder {
   fn parent(&self) -> &BaseBuilder {
     &self.base
@@ -110,19 +110,14 @@ pub struct BaseBuilder {
 }
 
 /// The supported SQL dialects.
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, Default, PartialEq, Eq)]
 pub enum Dialect {
   MySQL,
   SQLite,
+  #[default]
   Postgres,
 }
 
-impl Default for Dialect {
-  fn default() -> Self {
-    Dialect::Postgres
-  }
-}
-
 impl Builder for BaseBuilder {
   #[inline]
   fn build(self) -> (String, Vec<String>) {
@@ -180,7 +175,7 @@ impl BuilderExt for BaseBuilder {
     } else if is_func(s) || is_mod(s) {
       // Modifiers and aggregation functions that
       // were called without dialect information.
-      self.push_str(&s.replace("`", "\""));
+      self.push_str(s.replace('`', "\""));
     } else {
       self.push_str(s);
     }